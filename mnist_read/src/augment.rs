@@ -0,0 +1,155 @@
+use rand::Rng;
+use rand::SeedableRng;
+use rand_distr::{Distribution, Normal};
+use rand_xorshift::XorShiftRng;
+
+use crate::{IdxArray, IdxData, IdxError, Samples};
+use crate::emnist;
+
+/// Parameters of random transforms. Each image gets its own values drawn from the ranges.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Augmentation {
+    // rotation in [-rotation, rotation] degrees
+    pub rotation: f64,
+    // shift in [-shift, shift] pixels for each axis
+    pub shift: f64,
+    // zoom in [1-scale, 1+scale]
+    pub scale: f64,
+    // elastic distortion of Simard et al. alpha is the strength in pixels and
+    // sigma is the smoothness of the displacement field
+    pub elastic_alpha: f64,
+    pub elastic_sigma: f64,
+    // standard deviation of gaussian noise in intensity
+    pub noise: f64,
+    // probability of inverting intensity
+    pub invert: f64
+}
+
+impl Default for Augmentation {
+    /// No transform.
+    fn default() -> Augmentation {
+	Augmentation {
+	    rotation: 0.0,
+	    shift: 0.0,
+	    scale: 0.0,
+	    elastic_alpha: 0.0,
+	    elastic_sigma: 4.0,
+	    noise: 0.0,
+	    invert: 0.0
+	}
+    }
+}
+
+fn uniform<R:Rng>(rng:&mut R, max:f64) -> f64 {
+    if max > 0.0 { rng.gen_range(-max..=max) } else { 0.0 }
+}
+
+// 0 outside of the image
+fn bilinear(image:&[u8], rows:usize, cols:usize, x:f64, y:f64) -> f64 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x-x0, y-y0);
+    let pixel = |x:f64, y:f64| -> f64 {
+	if x < 0.0 || y < 0.0 || x >= cols as f64 || y >= rows as f64 { 0.0 }
+	else { image[y as usize*cols + x as usize] as f64 }
+    };
+    pixel(x0, y0)*(1.0-fx)*(1.0-fy) + pixel(x0+1.0, y0)*fx*(1.0-fy)
+	+ pixel(x0, y0+1.0)*(1.0-fx)*fy + pixel(x0+1.0, y0+1.0)*fx*fy
+}
+
+// separable gaussian blur of a rows*cols field. the outside is 0
+fn gaussian_blur(field:&[f64], rows:usize, cols:usize, sigma:f64) -> Vec<f64> {
+    let radius = (sigma*3.0).ceil() as isize;
+    let kernel:Vec<f64> = (-radius..=radius).map(|i| (-((i*i) as f64)/(2.0*sigma*sigma)).exp()).collect();
+    let total:f64 = kernel.iter().sum();
+    let blur = |src:&[f64], horizontal:bool| -> Vec<f64> {
+	let mut dst = vec![0.0; rows*cols];
+	for r in 0..rows as isize {
+	    for c in 0..cols as isize {
+		let mut sum = 0.0;
+		for (k, weight) in kernel.iter().enumerate() {
+		    let (sr, sc) = if horizontal { (r, c+k as isize-radius) } else { (r+k as isize-radius, c) };
+		    if sr >= 0 && sc >= 0 && sr < rows as isize && sc < cols as isize {
+			sum += src[(sr*cols as isize + sc) as usize]*weight;
+		    }
+		}
+		dst[(r*cols as isize + c) as usize] = sum/total;
+	    }
+	}
+	dst
+    };
+    blur(&blur(field, true), false)
+}
+
+/// Transforms an image of rows*cols pixels in row major order. Rotation, scale and shift
+/// are about the centre of the image, and they are sampled once after the elastic distortion.
+pub fn augment<R:Rng>(image:&[u8], rows:usize, cols:usize, augmentation:&Augmentation, rng:&mut R) -> Box<[u8]> {
+    let angle = uniform(rng, augmentation.rotation).to_radians();
+    let scale = 1.0 + uniform(rng, augmentation.scale.min(0.99));
+    let (shift_x, shift_y) = (uniform(rng, augmentation.shift), uniform(rng, augmentation.shift));
+
+    let (dx, dy) = if augmentation.elastic_alpha > 0.0 && augmentation.elastic_sigma > 0.0 {
+	let mut field = || -> Vec<f64> {
+	    let random:Vec<f64> = (0..rows*cols).map(|_| rng.gen_range(-1.0..1.0)).collect();
+	    gaussian_blur(&random, rows, cols, augmentation.elastic_sigma)
+		.into_iter().map(|d| d*augmentation.elastic_alpha).collect()
+	};
+	(field(), field())
+    }
+    else {
+	(vec![0.0; rows*cols], vec![0.0; rows*cols])
+    };
+
+    // output pixels are mapped back to the source by the inverse transform
+    let (cx, cy) = ((cols as f64 - 1.0)/2.0, (rows as f64 - 1.0)/2.0);
+    let (sin, cos) = angle.sin_cos();
+    let mut transformed:Vec<f64> = (0..rows*cols).map(|pos| {
+	let x = (pos % cols) as f64 + dx[pos] - cx - shift_x;
+	let y = (pos / cols) as f64 + dy[pos] - cy - shift_y;
+	let sx = (cos*x + sin*y)/scale + cx;
+	let sy = (-sin*x + cos*y)/scale + cy;
+	bilinear(image, rows, cols, sx, sy)
+    }).collect();
+
+    if augmentation.noise > 0.0 {
+	let normal = Normal::new(0.0, augmentation.noise).unwrap();
+	transformed.iter_mut().for_each(|v| *v += normal.sample(rng));
+    }
+    let invert = rng.gen_bool(augmentation.invert.clamp(0.0, 1.0));
+    transformed.into_iter().map(|v| {
+	let v = v.round().clamp(0.0, 255.0) as u8;
+	if invert { 255 - v } else { v }
+    }).collect()
+}
+
+/// Augmented copies of every sample, copies images per sample in the order of samples.
+/// Transforms are applied to upright images, and EMNIST images are transposed back
+/// to the stored layout, because to_upright is its own inverse.
+/// progress is called with the number of samples done after each sample.
+pub fn augment_samples<F:FnMut(usize)>(samples:&Samples, augmentation:&Augmentation, copies:usize, with_original:bool,
+				       seed:u64, mut progress:F) -> Result<(IdxArray, IdxArray),IdxError> {
+    let mut rng = XorShiftRng::seed_from_u64(seed);
+    let image_size = samples.rows*samples.cols;
+    let mut labels:Vec<u8> = Vec::new();
+    let mut images:Vec<u8> = Vec::new();
+    for no in 0..samples.len() {
+	if with_original {
+	    labels.push(samples.labels[no]);
+	    images.extend_from_slice(&samples.images[no*image_size..(no+1)*image_size]);
+	}
+	let upright = samples.image(no);
+	for _ in 0..copies {
+	    let augmented = augment(&upright, samples.rows, samples.cols, augmentation, &mut rng);
+	    labels.push(samples.labels[no]);
+	    if samples.kind.is_transposed() {
+		images.extend_from_slice(&emnist::to_upright(&augmented, samples.rows, samples.cols));
+	    }
+	    else {
+		images.extend_from_slice(&augmented);
+	    }
+	}
+	progress(no+1);
+    }
+    let count = labels.len();
+    Ok((IdxArray::new(vec![count], IdxData::UInt8(labels.into_boxed_slice()))?,
+	IdxArray::new(vec![count, samples.rows, samples.cols], IdxData::UInt8(images.into_boxed_slice()))?))
+}
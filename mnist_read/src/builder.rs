@@ -0,0 +1,243 @@
+use std::fs;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use crate::{IdxArray, IdxData, IdxError};
+
+/// Whether ink is made white like MNIST.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Invert {
+    // inverted when the border is lighter than the middle gray
+    Auto,
+    Always,
+    Never
+}
+
+#[derive(Debug,Clone)]
+pub struct BuildOptions {
+    // width and height of images
+    pub size: usize,
+    // the longer side of ink is fitted to box_size, and the rest is margin
+    pub box_size: usize,
+    pub invert: Invert
+}
+
+impl Default for BuildOptions {
+    /// Same as MNIST. Digits are fitted in 20x20 and placed in 28x28 by the centre of mass.
+    fn default() -> BuildOptions {
+	BuildOptions::for_size(28)
+    }
+}
+
+impl BuildOptions {
+    /// Images of size*size with the same margin ratio as MNIST, 20 in 28.
+    pub fn for_size(size:usize) -> BuildOptions {
+	BuildOptions {
+	    size: size,
+	    box_size: (size*20 + 14)/28,
+	    invert: Invert::Auto
+	}
+    }
+}
+
+/// Class folder and the label given to it.
+#[derive(Debug,Clone)]
+pub struct ClassFolder {
+    pub label: u8,
+    pub name: String,
+    pub path: PathBuf,
+    pub files: Vec<PathBuf>
+}
+
+/// Reads png as 8 bit grayscale of width*height. Transparent pixels are put on white.
+pub fn read_png<P:AsRef<Path>>(path:P) -> Result<(Vec<u8>, usize, usize),IdxError> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    // palettes and bit depths other than 8 are converted to 8 bit gray or rgb
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+    let (width, height) = (info.width as usize, info.height as usize);
+
+    let channels = info.color_type.samples();
+    let gray = |pixel:&[u8]| -> u8 {
+	let (luma, alpha) = match pixel.len() {
+	    1 => (pixel[0] as f64, 255.0),
+	    2 => (pixel[0] as f64, pixel[1] as f64),
+	    3 => (0.299*pixel[0] as f64 + 0.587*pixel[1] as f64 + 0.114*pixel[2] as f64, 255.0),
+	    _ => (0.299*pixel[0] as f64 + 0.587*pixel[1] as f64 + 0.114*pixel[2] as f64, pixel[3] as f64)
+	};
+	(luma*alpha/255.0 + 255.0 - alpha).round() as u8
+    };
+    let pixels = buf[..info.buffer_size()].chunks(channels).map(gray).collect();
+    Ok((pixels, width, height))
+}
+
+// resizes by averaging the covered area of source pixels
+fn resize(pixels:&[f64], width:usize, height:usize, new_width:usize, new_height:usize) -> Vec<f64> {
+    let (scale_x, scale_y) = (width as f64/new_width as f64, height as f64/new_height as f64);
+    // weights of source pixels over [start, end)
+    let coverage = |start:f64, end:f64, len:usize| -> Vec<(usize, f64)> {
+	let last = (end.ceil() as usize).min(len);
+	(start.floor() as usize..last).map(|i| {
+	    (i, (end.min(i as f64 + 1.0) - start.max(i as f64)).max(0.0))
+	}).filter(|&(_, w)| w > 0.0).collect()
+    };
+
+    let mut resized = vec![0.0; new_width*new_height];
+    for y in 0..new_height {
+	let rows = coverage(y as f64*scale_y, (y+1) as f64*scale_y, height);
+	for x in 0..new_width {
+	    let cols = coverage(x as f64*scale_x, (x+1) as f64*scale_x, width);
+	    let mut sum = 0.0;
+	    let mut weight = 0.0;
+	    for &(sy, wy) in rows.iter() {
+		for &(sx, wx) in cols.iter() {
+		    sum += pixels[sy*width+sx]*wx*wy;
+		    weight += wx*wy;
+		}
+	    }
+	    resized[y*new_width+x] = if weight > 0.0 { sum/weight } else { 0.0 };
+	}
+    }
+    resized
+}
+
+/// Converts a grayscale image like MNIST. Ink is cropped and fitted to box_size keeping
+/// the aspect ratio, and placed so that the centre of mass is at the centre of the image.
+pub fn preprocess(pixels:&[u8], width:usize, height:usize, options:&BuildOptions) -> Box<[u8]> {
+    let size = options.size;
+    let mut image = vec![0u8; size*size].into_boxed_slice();
+    if width == 0 || height == 0 {
+	return image;
+    }
+
+    let border:Vec<u8> = (0..width*height)
+	.filter(|pos| pos % width == 0 || pos % width == width-1 || pos / width == 0 || pos / width == height-1)
+	.map(|pos| pixels[pos]).collect();
+    let invert = match options.invert {
+	Invert::Always => true,
+	Invert::Never => false,
+	Invert::Auto => border.iter().map(|&p| p as usize).sum::<usize>() > 127*border.len()
+    };
+    let to_ink = |p:u8| if invert { 255 - p } else { p };
+
+    // paper is rarely pure white, so the median of the border is taken as the background
+    // and subtracted. faint ink under a tenth of the strongest is dropped as noise of paper.
+    let mut border_ink:Vec<u8> = border.into_iter().map(to_ink).collect();
+    border_ink.sort_unstable();
+    let background = border_ink[border_ink.len()/2] as f64;
+    let strongest = pixels.iter().map(|&p| to_ink(p) as f64 - background).fold(0.0, f64::max);
+    let ink:Vec<f64> = pixels.iter().map(|&p| {
+	let v = to_ink(p) as f64 - background;
+	if v > strongest/10.0 { v } else { 0.0 }
+    }).collect();
+
+    // bounding box of ink
+    let inked = |pos:usize| ink[pos] > 0.0;
+    let xs:Vec<usize> = (0..width*height).filter(|&pos| inked(pos)).map(|pos| pos % width).collect();
+    let ys:Vec<usize> = (0..width*height).filter(|&pos| inked(pos)).map(|pos| pos / width).collect();
+    let (left, right) = match (xs.iter().min(), xs.iter().max()) {
+	(Some(&left), Some(&right)) => (left, right+1),
+	_ => return image
+    };
+    let (top, bottom) = (*ys.iter().min().unwrap(), *ys.iter().max().unwrap()+1);
+    let (crop_width, crop_height) = (right-left, bottom-top);
+    let cropped:Vec<f64> = (top..bottom).flat_map(|y| ink[y*width+left..y*width+right].iter().cloned()).collect();
+
+    let box_size = options.box_size.min(size) as f64;
+    let scale = box_size/(crop_width.max(crop_height) as f64);
+    let new_width = ((crop_width as f64*scale).round() as usize).max(1);
+    let new_height = ((crop_height as f64*scale).round() as usize).max(1);
+    let resized = resize(&cropped, crop_width, crop_height, new_width, new_height);
+
+    // centre of mass of the fitted ink, and the offset to move it to the centre
+    let total:f64 = resized.iter().sum();
+    let (mut cx, mut cy) = (0.0, 0.0);
+    for (pos, &v) in resized.iter().enumerate() {
+	cx += (pos % new_width) as f64*v;
+	cy += (pos / new_width) as f64*v;
+    }
+    let (cx, cy) = if total > 0.0 { (cx/total, cy/total) } else { (new_width as f64/2.0, new_height as f64/2.0) };
+    let centre = (size as f64 - 1.0)/2.0;
+    let offset_x = (centre - cx).round() as isize;
+    let offset_y = (centre - cy).round() as isize;
+
+    for (pos, &v) in resized.iter().enumerate() {
+	let x = (pos % new_width) as isize + offset_x;
+	let y = (pos / new_width) as isize + offset_y;
+	// ink pushed out of the image by the shift is dropped
+	if x >= 0 && y >= 0 && (x as usize) < size && (y as usize) < size {
+	    image[y as usize*size + x as usize] = v.round().min(255.0) as u8;
+	}
+    }
+    image
+}
+
+/// Lists class folders and png files in them, both sorted by name. Folders named 0 to 255
+/// are labelled by the number, otherwise folders are labelled 0, 1, ... in the order of names.
+pub fn class_folders<P:AsRef<Path>>(dir:P) -> Result<Vec<ClassFolder>,IdxError> {
+    let mut folders:Vec<(String, PathBuf)> = Vec::new();
+    for entry in fs::read_dir(dir)? {
+	let entry = entry?;
+	if entry.file_type()?.is_dir() {
+	    folders.push((entry.file_name().to_string_lossy().to_string(), entry.path()));
+	}
+    }
+    if folders.is_empty() {
+	return Err(IdxError::Invalid("no class folder is found".to_string()));
+    }
+    let numbers:Option<Vec<u8>> = folders.iter().map(|(name, _)| name.parse::<u8>().ok()).collect();
+    match numbers {
+	Some(_) => folders.sort_by_key(|(name, _)| name.parse::<u8>().unwrap()),
+	None => folders.sort()
+    }
+    if numbers.is_some() {
+	// e.g. "7" and "07"
+	for pair in folders.windows(2) {
+	    let (a, b) = (&pair[0].0, &pair[1].0);
+	    if a.parse::<u8>().unwrap() == b.parse::<u8>().unwrap() {
+		return Err(IdxError::Invalid(format!("class folders {} and {} have the same label", a, b)));
+	    }
+	}
+    }
+    if folders.len() > 256 {
+	return Err(IdxError::Invalid(format!("{} class folders are more than 256 labels", folders.len())));
+    }
+
+    folders.into_iter().enumerate().map(|(i, (name, path))| {
+	let mut files:Vec<PathBuf> = Vec::new();
+	for entry in fs::read_dir(&path)? {
+	    let file = entry?.path();
+	    if file.is_file() && file.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("png")) {
+		files.push(file);
+	    }
+	}
+	files.sort();
+	Ok(ClassFolder {
+	    label: if numbers.is_some() { name.parse::<u8>().unwrap() } else { i as u8 },
+	    name: name,
+	    path: path,
+	    files: files
+	})
+    }).collect()
+}
+
+/// Builds labels of shape [n] and images of shape [n, size, size] from class folders.
+pub fn build_dataset(folders:&[ClassFolder], options:&BuildOptions) -> Result<(IdxArray, IdxArray),IdxError> {
+    let mut labels:Vec<u8> = Vec::new();
+    let mut images:Vec<u8> = Vec::new();
+    for folder in folders {
+	for file in folder.files.iter() {
+	    let (pixels, width, height) = read_png(file).map_err(|err| {
+		IdxError::Invalid(format!("failed to read {}. {}", file.display(), err))
+	    })?;
+	    images.extend_from_slice(&preprocess(&pixels, width, height, options));
+	    labels.push(folder.label);
+	}
+    }
+    let count = labels.len();
+    Ok((IdxArray::new(vec![count], IdxData::UInt8(labels.into_boxed_slice()))?,
+	IdxArray::new(vec![count, options.size, options.size], IdxData::UInt8(images.into_boxed_slice()))?))
+}
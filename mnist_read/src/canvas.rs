@@ -0,0 +1,110 @@
+use eframe::egui;
+
+use mnist_read::builder::{BuildOptions, Invert, preprocess};
+
+// strokes are rasterised at this multiple of the image size and then shrunk
+const SUPERSAMPLING:usize = 10;
+
+/// Strokes drawn with the mouse. Points are kept in [0, 1] of the canvas,
+/// so that they do not depend on the size shown.
+pub struct Canvas {
+    strokes: Vec<Vec<egui::Pos2>>,
+    // width of the pen relative to the canvas
+    pub pen_width: f32,
+    // incremented whenever strokes change
+    pub version: usize
+}
+
+impl Canvas {
+    pub fn new() -> Canvas {
+	Canvas {
+	    strokes: Vec::new(),
+	    pen_width: 0.08,
+	    version: 0
+	}
+    }
+
+    pub fn clear(&mut self) {
+	self.strokes.clear();
+	self.version += 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.strokes.is_empty()
+    }
+
+    /// Shows the canvas of size*size points. Drawing white on black like MNIST.
+    pub fn show(&mut self, ui:&mut egui::Ui, size:f32) {
+	let (response, painter) = ui.allocate_painter(egui::vec2(size, size), egui::Sense::drag());
+	let rect = response.rect;
+
+	if response.drag_started() {
+	    self.strokes.push(Vec::new());
+	}
+	if response.dragged() {
+	    if let (Some(pos), Some(stroke)) = (response.interact_pointer_pos(), self.strokes.last_mut()) {
+		let point = egui::pos2((pos.x - rect.min.x)/rect.width(), (pos.y - rect.min.y)/rect.height());
+		if stroke.last() != Some(&point) {
+		    stroke.push(point);
+		    self.version += 1;
+		}
+	    }
+	}
+
+	painter.rect_filled(rect, 0.0, egui::Color32::BLACK);
+	let stroke = egui::Stroke::new(self.pen_width*size, egui::Color32::WHITE);
+	for points in self.strokes.iter() {
+	    let points:Vec<egui::Pos2> = points.iter().map(|p| rect.min + egui::vec2(p.x*rect.width(), p.y*rect.height())).collect();
+	    match points.len() {
+		0 => (),
+		1 => { painter.circle_filled(points[0], stroke.width/2.0, stroke.color); },
+		_ => { painter.add(egui::Shape::line(points, stroke)); }
+	    }
+	}
+    }
+
+    /// Rasterises strokes into size*size pixels in row major order. Ink is fitted and
+    /// centred by the centre of mass like MNIST.
+    pub fn to_image(&self, size:usize) -> Box<[u8]> {
+	let large = size*SUPERSAMPLING;
+	let radius = self.pen_width*large as f32/2.0;
+	let mut pixels = vec![0u8; large*large];
+	for points in self.strokes.iter() {
+	    let points:Vec<(f32, f32)> = points.iter().map(|p| (p.x*large as f32, p.y*large as f32)).collect();
+	    // a single point is a segment of zero length
+	    let segments:Vec<((f32, f32), (f32, f32))> = match points.len() {
+		0 => Vec::new(),
+		1 => vec![(points[0], points[0])],
+		_ => points.windows(2).map(|w| (w[0], w[1])).collect()
+	    };
+	    for (a, b) in segments {
+		draw_segment(&mut pixels, large, a, b, radius);
+	    }
+	}
+	let options = BuildOptions {
+	    invert: Invert::Never,
+	    ..BuildOptions::for_size(size)
+	};
+	preprocess(&pixels, large, large, &options)
+    }
+}
+
+// paints pixels within radius of the segment. the edge is anti-aliased by a pixel
+fn draw_segment(pixels:&mut [u8], size:usize, a:(f32, f32), b:(f32, f32), radius:f32) {
+    let clip = |v:f32| v.max(0.0).min(size as f32) as usize;
+    let (left, right) = (clip(a.0.min(b.0) - radius - 1.0), clip(a.0.max(b.0) + radius + 2.0));
+    let (top, bottom) = (clip(a.1.min(b.1) - radius - 1.0), clip(a.1.max(b.1) + radius + 2.0));
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length2 = dx*dx + dy*dy;
+    for y in top..bottom {
+	for x in left..right {
+	    let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+	    let t = if length2 > 0.0 { (((px - a.0)*dx + (py - a.1)*dy)/length2).clamp(0.0, 1.0) } else { 0.0 };
+	    let (cx, cy) = (a.0 + t*dx - px, a.1 + t*dy - py);
+	    let coverage = (radius + 0.5 - (cx*cx + cy*cy).sqrt()).clamp(0.0, 1.0);
+	    let value = (coverage*255.0).round() as u8;
+	    let pixel = &mut pixels[y*size+x];
+	    *pixel = (*pixel).max(value);
+	}
+    }
+}
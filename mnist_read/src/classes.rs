@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::IdxError;
+
+/// Datasets distributed in idx format.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum DatasetKind {
+    Mnist,
+    // any split of EMNIST. class names are given by the mapping file of the split
+    Emnist,
+    FashionMnist,
+    Kmnist
+}
+
+impl DatasetKind {
+    pub fn from_name(name:&str) -> Option<DatasetKind> {
+	match name {
+	    "mnist" => Some(DatasetKind::Mnist),
+	    "emnist" => Some(DatasetKind::Emnist),
+	    "fashion" => Some(DatasetKind::FashionMnist),
+	    "kmnist" => Some(DatasetKind::Kmnist),
+	    _ => None
+	}
+    }
+
+    /// Guesses the dataset from the path of the images file, e.g. "emnist-letters-train-images-idx3-ubyte"
+    /// or "fashion/train-images-idx3-ubyte". MNIST is assumed when nothing matches.
+    pub fn detect<P:AsRef<Path>>(images_file:P) -> DatasetKind {
+	let path = images_file.as_ref().to_string_lossy().to_lowercase();
+	if path.contains("emnist") {
+	    DatasetKind::Emnist
+	}
+	else if path.contains("fashion") {
+	    DatasetKind::FashionMnist
+	}
+	else if path.contains("kmnist") {
+	    DatasetKind::Kmnist
+	}
+	else {
+	    DatasetKind::Mnist
+	}
+    }
+
+    /// EMNIST images are stored transposed.
+    pub fn is_transposed(&self) -> bool {
+	*self == DatasetKind::Emnist
+    }
+}
+
+const FASHION_MNIST_NAMES:[&str;10] = ["T-shirt/top", "Trouser", "Pullover", "Dress", "Coat",
+				       "Sandal", "Shirt", "Sneaker", "Bag", "Ankle boot"];
+
+// お, き, す, つ, な, は, ま, や, れ, を in romaji, since the default font of egui has no kana
+const KMNIST_NAMES:[&str;10] = ["o", "ki", "su", "tsu", "na", "ha", "ma", "ya", "re", "wo"];
+
+// the balanced split merges lower case letters which look like upper case ones
+const EMNIST_BALANCED_NAMES:[&str;47] = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9",
+					 "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M",
+					 "N", "O", "P", "Q", "R", "S", "T", "U", "V", "W", "X", "Y", "Z",
+					 "a", "b", "d", "e", "f", "g", "h", "n", "q", "r", "t"];
+
+/// Names of the labels of a dataset.
+#[derive(Debug,Clone,Default)]
+pub struct ClassNames {
+    names: BTreeMap<u8,String>
+}
+
+impl ClassNames {
+    fn from_names(names:&[&str]) -> ClassNames {
+	ClassNames {
+	    names: names.iter().enumerate().map(|(label, name)| (label as u8, name.to_string())).collect()
+	}
+    }
+
+    pub fn digits() -> ClassNames {
+	ClassNames::from_names(&["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"])
+    }
+
+    pub fn fashion_mnist() -> ClassNames {
+	ClassNames::from_names(&FASHION_MNIST_NAMES)
+    }
+
+    pub fn kmnist() -> ClassNames {
+	ClassNames::from_names(&KMNIST_NAMES)
+    }
+
+    /// Names of the balanced split of EMNIST, same as emnist-balanced-mapping.txt.
+    pub fn emnist_balanced() -> ClassNames {
+	ClassNames::from_names(&EMNIST_BALANCED_NAMES)
+    }
+
+    /// Parses an EMNIST mapping, in which each line is a label followed by ascii codes of the class.
+    /// The letters split has both cases, e.g. "1 65 97" is "Aa".
+    pub fn parse_mapping(mapping:&str) -> Result<ClassNames,IdxError> {
+	let mut names:BTreeMap<u8,String> = BTreeMap::new();
+	for (line_no, line) in mapping.lines().enumerate() {
+	    let mut fields = line.split_whitespace();
+	    let label = match fields.next() {
+		Some(label) => label,
+		None => {
+		    continue;
+		}
+	    };
+	    let invalid = |message:&str| IdxError::Invalid(format!("mapping line {}: {}", line_no+1, message));
+	    let label = label.parse::<u8>().map_err(|e| invalid(&e.to_string()))?;
+	    let name = fields.map(|code| {
+		code.parse::<u32>().ok().and_then(char::from_u32).ok_or_else(|| invalid(&format!("'{}' is not a character code", code)))
+	    }).collect::<Result<String,IdxError>>()?;
+	    if name.is_empty() {
+		return Err(invalid("no character code"));
+	    }
+	    names.insert(label, name);
+	}
+	Ok(ClassNames {
+	    names: names
+	})
+    }
+
+    pub fn read_mapping<P:AsRef<Path>>(mapping_file:P) -> Result<ClassNames,IdxError> {
+	ClassNames::parse_mapping(&fs::read_to_string(mapping_file)?)
+    }
+
+    /// Mapping file of the split next to EMNIST images file,
+    /// e.g. "emnist-byclass-mapping.txt" for "emnist-byclass-train-images-idx3-ubyte.gz".
+    pub fn emnist_mapping_file<P:AsRef<Path>>(images_file:P) -> Option<PathBuf> {
+	let path = images_file.as_ref();
+	let file_name = path.file_name()?.to_string_lossy().to_string();
+	let split = file_name.strip_prefix("emnist-")?.split('-').next()?;
+	Some(path.with_file_name(format!("emnist-{}-mapping.txt", split)))
+    }
+
+    /// Names for the dataset. EMNIST names are read from mapping_file, or the mapping file
+    /// next to the images file when it is not given. Names of the balanced split are used
+    /// when the mapping file next to the images file is not found, and the warning to report
+    /// is returned with them.
+    pub fn for_dataset<P:AsRef<Path>>(kind:DatasetKind, images_file:P,
+				      mapping_file:Option<&Path>) -> Result<(ClassNames,Option<String>),IdxError> {
+	match kind {
+	    DatasetKind::Mnist => Ok((ClassNames::digits(), None)),
+	    DatasetKind::FashionMnist => Ok((ClassNames::fashion_mnist(), None)),
+	    DatasetKind::Kmnist => Ok((ClassNames::kmnist(), None)),
+	    DatasetKind::Emnist => {
+		// the file given explicitly must exist
+		if let Some(mapping_file) = mapping_file {
+		    if !mapping_file.exists() {
+			return Err(IdxError::Invalid(format!("mapping file {} of EMNIST is not found", mapping_file.display())));
+		    }
+		    return Ok((ClassNames::read_mapping(mapping_file)?, None));
+		}
+		match ClassNames::emnist_mapping_file(images_file) {
+		    Some(mapping_file) if mapping_file.exists() => Ok((ClassNames::read_mapping(mapping_file)?, None)),
+		    Some(mapping_file) => {
+			let warning = format!("mapping file {} is not found. names of the balanced split are used", mapping_file.display());
+			Ok((ClassNames::emnist_balanced(), Some(warning)))
+		    },
+		    None => {
+			let warning = "mapping file of EMNIST is unknown. names of the balanced split are used".to_string();
+			Ok((ClassNames::emnist_balanced(), Some(warning)))
+		    }
+		}
+	    }
+	}
+    }
+
+    pub fn name(&self, label:u8) -> Option<&str> {
+	self.names.get(&label).map(String::as_str)
+    }
+}
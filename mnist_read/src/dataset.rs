@@ -0,0 +1,206 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, Cursor, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+
+use crate::{IdxData, IdxError, IdxHeader, IdxType};
+use crate::{decode_values, read_at, read_idx_header};
+
+/// Random access to the items of an idx file, that is the slices along the first dimension.
+/// Only the header is read when opened, and each item is read by seeking to it.
+pub struct IdxReader<R> {
+    reader: R,
+    header: IdxHeader
+}
+
+impl<R:Read+Seek> IdxReader<R> {
+    /// Reads the header, and checks that the stream holds exactly the values it describes.
+    pub fn new(mut reader:R) -> Result<IdxReader<R>,IdxError> {
+	reader.seek(SeekFrom::Start(0))?;
+	let header = read_idx_header(&mut reader)?;
+	if header.shape.is_empty() {
+	    return Err(IdxError::Invalid("0 dimensional idx has no items".to_string()));
+	}
+
+	let expected = header.header_size() + header.data_size();
+	let actual = reader.seek(SeekFrom::End(0))? as usize;
+	if actual < expected {
+	    return Err(IdxError::Truncated { expected: expected, actual: actual });
+	}
+	if actual > expected {
+	    return Err(IdxError::TrailingData { expected: expected, actual: actual });
+	}
+
+	Ok(IdxReader {
+	    reader: reader,
+	    header: header
+	})
+    }
+
+    pub fn header(&self) -> &IdxHeader {
+	&self.header
+    }
+
+    /// Number of items, the first dimension.
+    pub fn len(&self) -> usize {
+	self.header.shape[0]
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.len() == 0
+    }
+
+    /// Shape of each item, the dimensions after the first.
+    pub fn item_shape(&self) -> &[usize] {
+	&self.header.shape[1..]
+    }
+
+    fn item_size(&self) -> usize {
+	self.item_shape().iter().product::<usize>()*self.header.value_type.size()
+    }
+
+    pub fn read_item(&mut self, index:usize) -> Result<IdxData,IdxError> {
+	if index >= self.len() {
+	    return Err(IdxError::OutOfRange { index: index, len: self.len() });
+	}
+	self.read_items(index..index+1)
+    }
+
+    /// Values of the items in the range, one after another.
+    pub fn read_items(&mut self, range:Range<usize>) -> Result<IdxData,IdxError> {
+	if range.start > range.end {
+	    return Err(IdxError::Invalid(format!("range {}..{} is reversed", range.start, range.end)));
+	}
+	if range.end > self.len() {
+	    return Err(IdxError::OutOfRange { index: range.end, len: self.len() });
+	}
+
+	let offset = self.header.header_size() + range.start*self.item_size();
+	self.reader.seek(SeekFrom::Start(offset as u64))?;
+	// the stream may be shortened after opened
+	let mut bytes = vec![0u8; range.len()*self.item_size()];
+	read_at(&mut self.reader, &mut bytes, offset)?;
+	Ok(decode_values(self.header.value_type, bytes))
+    }
+}
+
+/// Seekable source of an idx file. gzip files can not be seeked, so they are decompressed in memory.
+pub enum IdxSource {
+    File(BufReader<File>),
+    Memory(Cursor<Vec<u8>>)
+}
+
+impl IdxSource {
+    pub fn open<P:AsRef<Path>>(path:P) -> Result<IdxSource,IdxError> {
+	let mut reader = BufReader::new(File::open(path)?);
+	let is_gzip = reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+	if is_gzip {
+	    let mut bytes:Vec<u8> = Vec::new();
+	    GzDecoder::new(reader).read_to_end(&mut bytes)?;
+	    Ok(IdxSource::Memory(Cursor::new(bytes)))
+	}
+	else {
+	    Ok(IdxSource::File(reader))
+	}
+    }
+}
+
+impl Read for IdxSource {
+    fn read(&mut self, buf:&mut [u8]) -> io::Result<usize> {
+	match self {
+	    IdxSource::File(reader) => reader.read(buf),
+	    IdxSource::Memory(reader) => reader.read(buf)
+	}
+    }
+}
+
+impl Seek for IdxSource {
+    fn seek(&mut self, pos:SeekFrom) -> io::Result<u64> {
+	match self {
+	    IdxSource::File(reader) => reader.seek(pos),
+	    IdxSource::Memory(reader) => reader.seek(pos)
+	}
+    }
+}
+
+/// Pairs of a label and an image read on demand. Labels must be 1 dimensional u8,
+/// and images must be 3 dimensional u8 of the same count.
+pub struct IdxDataset<R> {
+    labels: IdxReader<R>,
+    images: IdxReader<R>
+}
+
+impl IdxDataset<IdxSource> {
+    pub fn open<P:AsRef<Path>>(labels_file:P, images_file:P) -> Result<IdxDataset<IdxSource>,IdxError> {
+	IdxDataset::new(IdxSource::open(labels_file)?, IdxSource::open(images_file)?)
+    }
+}
+
+impl<R:Read+Seek> IdxDataset<R> {
+    pub fn new(labels:R, images:R) -> Result<IdxDataset<R>,IdxError> {
+	let labels = IdxReader::new(labels)?;
+	let images = IdxReader::new(images)?;
+
+	let (label_header, image_header) = (labels.header(), images.header());
+	if label_header.value_type != IdxType::UInt8 || label_header.shape.len() != 1
+	    || image_header.value_type != IdxType::UInt8 || image_header.shape.len() != 3 {
+	    return Err(IdxError::Invalid("labels must be 1 dimensional u8 and images must be 3 dimensional u8".to_string()));
+	}
+	if labels.len() != images.len() {
+	    return Err(IdxError::Invalid(format!("{} labels do not match {} images", labels.len(), images.len())));
+	}
+
+	Ok(IdxDataset {
+	    labels: labels,
+	    images: images
+	})
+    }
+
+    pub fn len(&self) -> usize {
+	self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.labels.is_empty()
+    }
+
+    pub fn rows(&self) -> usize {
+	self.images.item_shape()[0]
+    }
+
+    pub fn cols(&self) -> usize {
+	self.images.item_shape()[1]
+    }
+
+    pub fn label(&mut self, index:usize) -> Result<u8,IdxError> {
+	match self.labels.read_item(index)? {
+	    IdxData::UInt8(values) => Ok(values[0]),
+	    _ => unreachable!()
+	}
+    }
+
+    /// Pixels of the image in the stored layout, rows*cols in row major order.
+    pub fn image(&mut self, index:usize) -> Result<Box<[u8]>,IdxError> {
+	match self.images.read_item(index)? {
+	    IdxData::UInt8(values) => Ok(values),
+	    _ => unreachable!()
+	}
+    }
+
+    pub fn get(&mut self, index:usize) -> Result<(u8, Box<[u8]>),IdxError> {
+	Ok((self.label(index)?, self.image(index)?))
+    }
+
+    /// All labels, and all images one after another.
+    pub fn read_all(&mut self) -> Result<(Box<[u8]>, Box<[u8]>),IdxError> {
+	let len = self.len();
+	match (self.labels.read_items(0..len)?, self.images.read_items(0..len)?) {
+	    (IdxData::UInt8(labels), IdxData::UInt8(images)) => Ok((labels, images)),
+	    _ => unreachable!()
+	}
+    }
+}
@@ -0,0 +1,23 @@
+/// EMNIST images are stored transposed. Rotates the image right by 90 degrees
+/// and mirrors it, so that it is shown upright as MNIST.
+/// image is rows*cols pixels in row major order, and rows must be equal to cols.
+pub fn to_upright(image:&[u8], rows:usize, cols:usize) -> Box<[u8]> {
+    let mut reversed_image:Box<[u8]> = vec![0; rows*cols].into_boxed_slice();
+    let mut upright_image:Box<[u8]> = vec![0; rows*cols].into_boxed_slice();
+
+    // 右90度回転
+    for c in 0..cols {
+	for r in 0..rows {
+	    let src_pos = (cols-1-r)*rows+c;
+	    reversed_image[c*rows+r] = image[src_pos];
+	}
+    }
+    // 鏡像反転
+    for c in 0..cols {
+	for r in 0..rows {
+	    let src_pos = c*rows + (rows-1-r);
+	    upright_image[c*rows+r] = reversed_image[src_pos];
+	}
+    }
+    upright_image
+}
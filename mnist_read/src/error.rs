@@ -0,0 +1,89 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum IdxError {
+    // the first two bytes of magic are not zero
+    BadMagic(u32),
+    // type byte in the magic
+    UnsupportedType(u8),
+    // the file is longer than the header describes
+    TrailingData { expected: usize, actual: usize },
+    // bytes required and bytes actually read
+    Truncated { expected: usize, actual: usize },
+    // index of an item beyond the number of items
+    OutOfRange { index: usize, len: usize },
+    // shape or values which can not be written, or do not match the other file
+    Invalid(String),
+    Io(io::Error),
+    Png(png::EncodingError),
+    PngDecode(png::DecodingError)
+}
+
+impl IdxError {
+    /// Exit status of mnist_read. 2 is taken by invalid arguments, and 11 and 13 by
+    /// ModelError of the weight file.
+    pub fn exit_code(&self) -> i32 {
+	match self {
+	    IdxError::BadMagic(_) => 3,
+	    IdxError::UnsupportedType(_) => 4,
+	    IdxError::TrailingData { .. } => 5,
+	    IdxError::Truncated { .. } => 6,
+	    IdxError::Invalid(_) => 7,
+	    IdxError::Io(_) => 8,
+	    IdxError::Png(_) => 9,
+	    IdxError::PngDecode(_) => 10,
+	    IdxError::OutOfRange { .. } => 12
+	}
+    }
+}
+
+impl fmt::Display for IdxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    IdxError::BadMagic(magic) => write!(f, "magic {:08x} is invalid", magic),
+	    IdxError::UnsupportedType(code) => write!(f, "unknown value type {:02x}", code),
+	    IdxError::TrailingData { expected, actual } => {
+		write!(f, "file size is too long. {} bytes are expected, but {} bytes are found", expected, actual)
+	    },
+	    IdxError::Truncated { expected, actual } => {
+		write!(f, "file size is too short. {} bytes are required, but {} bytes are read", expected, actual)
+	    },
+	    IdxError::OutOfRange { index, len } => write!(f, "index {} is out of {} items", index, len),
+	    IdxError::Invalid(message) => write!(f, "{}", message),
+	    IdxError::Io(err) => write!(f, "{}", err),
+	    IdxError::Png(err) => write!(f, "{}", err),
+	    IdxError::PngDecode(err) => write!(f, "{}", err)
+	}
+    }
+}
+
+impl error::Error for IdxError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+	match self {
+	    IdxError::Io(err) => Some(err),
+	    IdxError::Png(err) => Some(err),
+	    IdxError::PngDecode(err) => Some(err),
+	    _ => None
+	}
+    }
+}
+
+impl From<io::Error> for IdxError {
+    fn from(err:io::Error) -> IdxError {
+	IdxError::Io(err)
+    }
+}
+
+impl From<png::EncodingError> for IdxError {
+    fn from(err:png::EncodingError) -> IdxError {
+	IdxError::Png(err)
+    }
+}
+
+impl From<png::DecodingError> for IdxError {
+    fn from(err:png::DecodingError) -> IdxError {
+	IdxError::PngDecode(err)
+    }
+}
@@ -0,0 +1,193 @@
+use std::cmp;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::mem;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::{IdxError, Samples};
+
+/// Samples to export.
+#[derive(Debug,Clone)]
+pub enum Selection {
+    // ranges of sample numbers
+    Ranges(Vec<Range<usize>>),
+    // the first n samples of each class
+    PerClass(usize)
+}
+
+/// Parses ranges such as "0-99,120,200-209". Both ends are included.
+pub fn parse_ranges(ranges:&str) -> Result<Vec<Range<usize>>,String> {
+    ranges.split(',').map(|range| {
+	let parse = |no:&str| no.trim().parse::<usize>().map_err(|e| format!("{} in '{}'", e, range));
+	let (start, end) = match range.split_once('-') {
+	    Some((start, end)) => (parse(start)?, parse(end)?),
+	    None => {
+		let no = parse(range)?;
+		(no, no)
+	    }
+	};
+	if start > end {
+	    return Err(format!("'{}' is reversed", range));
+	}
+	match end.checked_add(1) {
+	    Some(end) => Ok(start..end),
+	    None => Err(format!("'{}' is too large", range))
+	}
+    }).collect()
+}
+
+/// Numbers of the selected samples in the order of selection.
+/// A number in overlapping ranges is selected once, where it first appears.
+pub fn select(samples:&Samples, selection:&Selection) -> Result<Vec<usize>,IdxError> {
+    match selection {
+	Selection::Ranges(ranges) => {
+	    if let Some(range) = ranges.iter().find(|range| range.end > samples.len()) {
+		return Err(IdxError::Invalid(format!("{}-{} is out of {} samples", range.start, range.end-1, samples.len())));
+	    }
+	    let mut selected = vec![false; samples.len()];
+	    Ok(ranges.iter().flat_map(|range| range.clone())
+	       .filter(|&no| !mem::replace(&mut selected[no], true)).collect())
+	},
+	Selection::PerClass(count) => {
+	    let mut nos:Vec<usize> = Vec::new();
+	    for label in samples.classes() {
+		nos.extend((0..samples.len()).filter(|&no| samples.labels[no] == label).take(*count));
+	    }
+	    Ok(nos)
+	}
+    }
+}
+
+/// Writes 8 bit grayscale pixels in row major order as png.
+pub fn write_png<P:AsRef<Path>>(path:P, pixels:&[u8], width:usize, height:usize) -> Result<(),IdxError> {
+    let w = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    // IEND is written here. dropping the writer would ignore the error
+    writer.finish()?;
+    Ok(())
+}
+
+// class name quoted for csv. empty when the name is unknown
+fn csv_name(samples:&Samples, no:usize) -> String {
+    match samples.names.name(samples.labels[no]) {
+	Some(name) => format!("\"{}\"", name.replace('"', "\"\"")),
+	None => String::new()
+    }
+}
+
+/// Writes each sample to "<no>_<label>.png" in the directory, and the list of them to "labels.csv".
+pub fn export_images<P:AsRef<Path>>(samples:&Samples, nos:&[usize], dir:P) -> Result<(),IdxError> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+
+    let mut csv = BufWriter::new(File::create(dir.join("labels.csv"))?);
+    writeln!(csv, "file,no,label,name")?;
+    for &no in nos {
+	let file = format!("{:05}_{}.png", no, samples.labels[no]);
+	write_png(dir.join(&file), &samples.image(no), samples.cols, samples.rows)?;
+	writeln!(csv, "{},{},{},{}", file, no, samples.labels[no], csv_name(samples, no))?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+/// Writes samples into one png of columns images per row, and the position of each sample
+/// to the csv of the same name.
+pub fn export_sprite_sheet<P:AsRef<Path>>(samples:&Samples, nos:&[usize], path:P, columns:usize) -> Result<(),IdxError> {
+    if nos.is_empty() {
+	return Err(IdxError::Invalid("no sample is selected".to_string()));
+    }
+    let columns = cmp::max(cmp::min(columns, nos.len()), 1);
+    let sheet_rows = (nos.len() + columns - 1)/columns;
+    let width = columns*samples.cols;
+    let height = sheet_rows*samples.rows;
+
+    let mut sheet = vec![0u8; width*height];
+    let mut csv = BufWriter::new(File::create(path.as_ref().with_extension("csv"))?);
+    writeln!(csv, "index,no,label,name,x,y")?;
+    for (index, &no) in nos.iter().enumerate() {
+	let x = (index % columns)*samples.cols;
+	let y = (index / columns)*samples.rows;
+	let image = samples.image(no);
+	for r in 0..samples.rows {
+	    let dst_pos = (y+r)*width + x;
+	    sheet[dst_pos..dst_pos+samples.cols].copy_from_slice(&image[r*samples.cols..(r+1)*samples.cols]);
+	}
+	writeln!(csv, "{},{},{},{},{},{}", index, no, samples.labels[no], csv_name(samples, no), x, y)?;
+    }
+    csv.flush()?;
+
+    write_png(path, &sheet, width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::{ClassNames, DatasetKind};
+
+    // 1x1 images labelled by the list
+    fn labelled(labels:&[u8]) -> Samples {
+	Samples {
+	    labels: labels.to_vec().into_boxed_slice(),
+	    images: vec![0; labels.len()].into_boxed_slice(),
+	    rows: 1,
+	    cols: 1,
+	    kind: DatasetKind::Mnist,
+	    names: ClassNames::digits()
+	}
+    }
+
+    #[test]
+    fn ranges() {
+	assert_eq!(parse_ranges("0-2,5, 7 - 8").unwrap(), vec![0..3, 5..6, 7..9]);
+	assert_eq!(parse_ranges("4-4").unwrap(), vec![4..5]);
+    }
+
+    #[test]
+    fn malformed_ranges() {
+	for ranges in ["", "a", "1-", "-1", "1-2-3", "0-2,", "1.5", "0-99999999999999999999999"].iter() {
+	    assert!(parse_ranges(ranges).is_err(), "'{}' is accepted", ranges);
+	}
+	let message = parse_ranges("0-1,x").unwrap_err();
+	assert!(message.contains("'x'"), "{}", message);
+	// the end past the range does not overflow
+	assert!(parse_ranges(&format!("0-{}", usize::MAX)).is_err());
+    }
+
+    #[test]
+    fn reversed_range() {
+	assert_eq!(parse_ranges("5-3").unwrap_err(), "'5-3' is reversed");
+    }
+
+    #[test]
+    fn out_of_range() {
+	let samples = labelled(&[0, 1, 2, 3]);
+	assert_eq!(select(&samples, &Selection::Ranges(vec![3..4])).unwrap(), vec![3]);
+	match select(&samples, &Selection::Ranges(vec![0..1, 2..6])) {
+	    Err(IdxError::Invalid(message)) => assert_eq!(message, "2-5 is out of 4 samples"),
+	    other => panic!("unexpected {:?}", other)
+	}
+	assert!(select(&labelled(&[]), &Selection::Ranges(vec![0..1])).is_err());
+    }
+
+    #[test]
+    fn overlapping_ranges() {
+	let samples = labelled(&[0; 10]);
+	let ranges = parse_ranges("6-8,0-2,1-3,7").unwrap();
+	assert_eq!(select(&samples, &Selection::Ranges(ranges)).unwrap(), vec![6, 7, 8, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn per_class() {
+	let samples = labelled(&[2, 0, 2, 1, 0, 2]);
+	assert_eq!(select(&samples, &Selection::PerClass(2)).unwrap(), vec![1, 4, 3, 0, 2]);
+	assert_eq!(select(&samples, &Selection::PerClass(0)).unwrap(), Vec::<usize>::new());
+    }
+}
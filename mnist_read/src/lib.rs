@@ -0,0 +1,329 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter, ErrorKind};
+use std::path::Path;
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+mod dataset;
+mod error;
+mod samples;
+pub mod augment;
+pub mod builder;
+pub mod classes;
+pub mod emnist;
+pub mod export;
+pub mod model;
+pub mod stats;
+pub use dataset::{IdxDataset, IdxReader, IdxSource};
+pub use error::IdxError;
+pub use samples::Samples;
+
+/// Value type given by the third byte of magic.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum IdxType {
+    UInt8, Int8, Int16, Int32, Float32, Float64
+}
+
+impl IdxType {
+    pub fn from_code(code:u8) -> Result<IdxType,IdxError> {
+	match code {
+	    0x08 => Ok(IdxType::UInt8),
+	    0x09 => Ok(IdxType::Int8),
+	    0x0b => Ok(IdxType::Int16),
+	    0x0c => Ok(IdxType::Int32),
+	    0x0d => Ok(IdxType::Float32),
+	    0x0e => Ok(IdxType::Float64),
+	    _ => Err(IdxError::UnsupportedType(code))
+	}
+    }
+
+    pub fn code(&self) -> u8 {
+	match self {
+	    IdxType::UInt8 => 0x08,
+	    IdxType::Int8 => 0x09,
+	    IdxType::Int16 => 0x0b,
+	    IdxType::Int32 => 0x0c,
+	    IdxType::Float32 => 0x0d,
+	    IdxType::Float64 => 0x0e
+	}
+    }
+
+    pub fn size(&self) -> usize {
+	match self {
+	    IdxType::UInt8 | IdxType::Int8 => 1,
+	    IdxType::Int16 => 2,
+	    IdxType::Int32 | IdxType::Float32 => 4,
+	    IdxType::Float64 => 8
+	}
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct IdxHeader {
+    pub value_type: IdxType,
+    // the first dimension is the number of items, e.g. [60000, 28, 28] for images
+    pub shape: Vec<usize>
+}
+
+impl IdxHeader {
+    /// Bytes of magic and dimensions.
+    pub fn header_size(&self) -> usize {
+	4 + 4*self.shape.len()
+    }
+
+    /// Bytes of the values following the header, or None when they can not be addressed.
+    pub fn checked_data_size(&self) -> Option<usize> {
+	self.shape.iter().try_fold(self.value_type.size(), |size, &dim| size.checked_mul(dim))
+	    .filter(|&size| size <= isize::MAX as usize)
+    }
+
+    /// Bytes of the values following the header, saturated at usize::MAX.
+    /// Headers read by read_idx_header never saturate.
+    pub fn data_size(&self) -> usize {
+	self.checked_data_size().unwrap_or(usize::MAX)
+    }
+}
+
+/// Values in the order of the file. Multi-byte values are stored in big endian.
+#[derive(Debug,Clone,PartialEq)]
+pub enum IdxData {
+    UInt8(Box<[u8]>),
+    Int8(Box<[i8]>),
+    Int16(Box<[i16]>),
+    Int32(Box<[i32]>),
+    Float32(Box<[f32]>),
+    Float64(Box<[f64]>)
+}
+
+macro_rules! dispatch_idx_data {
+    ($data:expr, $values:ident => $body:expr) => {
+	match $data {
+	    $crate::IdxData::UInt8($values) => $body,
+	    $crate::IdxData::Int8($values) => $body,
+	    $crate::IdxData::Int16($values) => $body,
+	    $crate::IdxData::Int32($values) => $body,
+	    $crate::IdxData::Float32($values) => $body,
+	    $crate::IdxData::Float64($values) => $body
+	}
+    }
+}
+
+impl IdxData {
+    pub fn value_type(&self) -> IdxType {
+	match self {
+	    IdxData::UInt8(_) => IdxType::UInt8,
+	    IdxData::Int8(_) => IdxType::Int8,
+	    IdxData::Int16(_) => IdxType::Int16,
+	    IdxData::Int32(_) => IdxType::Int32,
+	    IdxData::Float32(_) => IdxType::Float32,
+	    IdxData::Float64(_) => IdxType::Float64
+	}
+    }
+
+    pub fn len(&self) -> usize {
+	dispatch_idx_data!(self, values => values.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.len() == 0
+    }
+
+    pub fn as_u8(&self) -> Option<&[u8]> {
+	match self {
+	    IdxData::UInt8(values) => Some(values),
+	    _ => None
+	}
+    }
+
+    pub fn to_f64(&self) -> Vec<f64> {
+	dispatch_idx_data!(self, values => values.iter().map(|&v| v as f64).collect())
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct IdxArray {
+    pub shape: Vec<usize>,
+    pub data: IdxData
+}
+
+impl IdxArray {
+    /// Checks that the shape holds all values.
+    pub fn new(shape:Vec<usize>, data:IdxData) -> Result<IdxArray,IdxError> {
+	let num_of_items:usize = shape.iter().product();
+	if num_of_items != data.len() {
+	    return Err(IdxError::Invalid(format!("shape {:?} does not match {} values", shape, data.len())));
+	}
+	Ok(IdxArray {
+	    shape: shape,
+	    data: data
+	})
+    }
+
+    pub fn header(&self) -> IdxHeader {
+	IdxHeader {
+	    value_type: self.data.value_type(),
+	    shape: self.shape.clone()
+	}
+    }
+}
+
+// fills the buffer, which starts offset bytes from the beginning of the idx file.
+// when the stream ends, Truncated counts the bytes of the file, not of the buffer.
+pub(crate) fn read_at<R:Read>(f:&mut R, buffer:&mut [u8], offset:usize) -> Result<(),IdxError> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+	match f.read(&mut buffer[filled..]) {
+	    Ok(0) => {
+		return Err(IdxError::Truncated { expected: offset + buffer.len(), actual: offset + filled });
+	    },
+	    Ok(readsize) => {
+		filled += readsize;
+	    },
+	    Err(err) if err.kind() == ErrorKind::Interrupted => (),
+	    Err(err) => {
+		return Err(IdxError::Io(err));
+	    }
+	}
+    }
+    Ok(())
+}
+
+/// Reads magic and dimensions, then leaves the reader at the beginning of values.
+pub fn read_idx_header<R:Read>(f:&mut R) -> Result<IdxHeader,IdxError> {
+    let mut magic = [0u8;4];
+    read_at(f, &mut magic, 0)?;
+    if magic[0] != 0 || magic[1] != 0 {
+	return Err(IdxError::BadMagic(u32::from_be_bytes(magic)));
+    }
+    let value_type = IdxType::from_code(magic[2])?;
+
+    let mut dims = vec![0u8; 4*magic[3] as usize];
+    read_at(f, &mut dims, magic.len())?;
+    let shape = dims.chunks_exact(4).map(|dim| u32::from_be_bytes(dim.try_into().unwrap()) as usize).collect();
+
+    let header = IdxHeader {
+	value_type: value_type,
+	shape: shape
+    };
+    if header.checked_data_size().is_none() {
+	return Err(IdxError::Invalid(format!("shape {:?} is too large", header.shape)));
+    }
+    Ok(header)
+}
+
+macro_rules! decode_be {
+    ($t:ty, $bytes:expr) => {
+	$bytes.chunks_exact(std::mem::size_of::<$t>()).map(|raw| <$t>::from_be_bytes(raw.try_into().unwrap())).collect()
+    }
+}
+
+// values from big endian bytes
+pub(crate) fn decode_values(value_type:IdxType, bytes:Vec<u8>) -> IdxData {
+    match value_type {
+	IdxType::UInt8 => IdxData::UInt8(bytes.into_boxed_slice()),
+	IdxType::Int8 => IdxData::Int8(bytes.iter().map(|&b| b as i8).collect()),
+	IdxType::Int16 => IdxData::Int16(decode_be!(i16, bytes)),
+	IdxType::Int32 => IdxData::Int32(decode_be!(i32, bytes)),
+	IdxType::Float32 => IdxData::Float32(decode_be!(f32, bytes)),
+	IdxType::Float64 => IdxData::Float64(decode_be!(f64, bytes))
+    }
+}
+
+/// Reads values described by the header, which is read by read_idx_header.
+pub fn read_idx_values<R:Read>(f:&mut R, header:&IdxHeader) -> Result<IdxData,IdxError> {
+    let size = header.checked_data_size()
+	.ok_or_else(|| IdxError::Invalid(format!("shape {:?} is too large", header.shape)))?;
+    // the buffer grows with the bytes actually read, not with the size the header claims
+    let mut bytes:Vec<u8> = Vec::new();
+    f.take(size as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < size {
+	let offset = header.header_size();
+	return Err(IdxError::Truncated { expected: offset + size, actual: offset + bytes.len() });
+    }
+    Ok(decode_values(header.value_type, bytes))
+}
+
+/// Reads an idx stream to the end. Bytes left after the values are an error.
+pub fn read_idx_from<R:Read>(mut reader:R) -> Result<IdxArray,IdxError> {
+    let header = read_idx_header(&mut reader)?;
+    let data = read_idx_values(&mut reader, &header)?;
+
+    let trailing = io::copy(&mut reader, &mut io::sink())? as usize;
+    if trailing > 0 {
+	let expected = header.header_size() + header.data_size();
+	return Err(IdxError::TrailingData { expected: expected, actual: expected + trailing });
+    }
+
+    Ok(IdxArray {
+	shape: header.shape,
+	data: data
+    })
+}
+
+/// Opens an idx file. gzip files, as distributed on the MNIST site, are decompressed transparently.
+pub fn open_idx<P:AsRef<Path>>(path:P) -> Result<Box<dyn Read>,IdxError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    // gzip is detected by its magic, since files are often renamed
+    let is_gzip = reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    if is_gzip {
+	Ok(Box::new(BufReader::new(GzDecoder::new(reader))))
+    }
+    else {
+	Ok(Box::new(reader))
+    }
+}
+
+pub fn read_idx<P:AsRef<Path>>(path:P) -> Result<IdxArray,IdxError> {
+    read_idx_from(open_idx(path)?)
+}
+
+pub fn write_idx_to<W:Write>(w:&mut W, array:&IdxArray) -> Result<(),IdxError> {
+    if array.shape.len() > u8::MAX as usize {
+	return Err(IdxError::Invalid(format!("{} dimensions can not be written", array.shape.len())));
+    }
+    let num_of_items:usize = array.shape.iter().product();
+    if num_of_items != array.data.len() {
+	return Err(IdxError::Invalid(format!("shape {:?} does not match {} values", array.shape, array.data.len())));
+    }
+
+    let mut header = vec![0, 0, array.data.value_type().code(), array.shape.len() as u8];
+    for &dim in array.shape.iter() {
+	if dim > u32::MAX as usize {
+	    return Err(IdxError::Invalid(format!("dimension {} is too large", dim)));
+	}
+	header.extend_from_slice(&(dim as u32).to_be_bytes());
+    }
+    w.write_all(&header)?;
+
+    let bytes:Vec<u8> = match &array.data {
+	IdxData::UInt8(values) => values.to_vec(),
+	IdxData::Int8(values) => values.iter().map(|&v| v as u8).collect(),
+	IdxData::Int16(values) => values.iter().flat_map(|v| v.to_be_bytes()).collect(),
+	IdxData::Int32(values) => values.iter().flat_map(|v| v.to_be_bytes()).collect(),
+	IdxData::Float32(values) => values.iter().flat_map(|v| v.to_be_bytes()).collect(),
+	IdxData::Float64(values) => values.iter().flat_map(|v| v.to_be_bytes()).collect()
+    };
+    w.write_all(&bytes)?;
+    Ok(())
+}
+
+/// Writes an idx file. The file is compressed by gzip when the name ends with ".gz".
+pub fn write_idx<P:AsRef<Path>>(path:P, array:&IdxArray) -> Result<(),IdxError> {
+    let is_gzip = path.as_ref().extension().map_or(false, |ext| ext == "gz");
+    let w = BufWriter::new(File::create(path)?);
+    if is_gzip {
+	let mut encoder = GzEncoder::new(w, Compression::default());
+	write_idx_to(&mut encoder, array)?;
+	encoder.finish()?.flush()?;
+    }
+    else {
+	let mut w = w;
+	write_idx_to(&mut w, array)?;
+	w.flush()?;
+    }
+    Ok(())
+}
@@ -1,251 +1,325 @@
-use std::{env,mem};
-use std::fs::File;
-use std::io::prelude::*;
-use std::io::{BufReader, Read, SeekFrom};
+use std::{fmt,process};
+use std::path::Path;
 
-use binary_layout::prelude::*;
-use clap::{App,Arg};
+use clap::{App,Arg,ArgGroup,ArgMatches};
 
-use eframe::egui;
-use egui_extras::RetainedImage;
+mod canvas;
+mod viewer;
 
-define_layout!(minst_label_header, BigEndian, {
-    magic: u32,
-    num_of_images: u32,
-});
-
-define_layout!(minst_image_header, BigEndian, {
-    magic: u32,
-    num_of_images: u32,
-    row: u32,
-    col: u32
-});
+use mnist_read::{IdxDataset, Samples, write_idx};
+use mnist_read::builder::{BuildOptions, Invert, build_dataset, class_folders};
+use mnist_read::classes::{ClassNames, DatasetKind};
+use mnist_read::export::{Selection, export_images, export_sprite_sheet, parse_ranges, select};
+use mnist_read::model::Network;
+use mnist_read::stats;
+use viewer::GuiContext;
 
 #[derive(Debug,Clone)]
 struct AppContext {
     labels_file: String,
     images_file: String,
     no_of_image: u32,
-    row_of_image: u32,
-    col_of_image: u32,
-    label_of_image: u8,
-    image_data: Option<Box<[u8]>>,
-    is_need_revered: bool
+    weight_file: Option<String>
 }
 
-struct GuiContext {
-    appCtx : AppContext
+// IdxError and ModelError give their own exit status
+fn exit_with<E:fmt::Display>(err:E, code:i32) -> ! {
+    eprintln!("Error {}", err);
+    process::exit(code);
 }
 
-impl eframe::App for GuiContext {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-	let label_table:[char;47] = ['0','1','2','3','4','5','6','7','8','9',
-				     'A','B','C','D','E','F','G','H','I','J','K','L','M','N','O','P','Q','R','S','T','U','V','W','X','Y','Z',
-				     'a','b',    'd','e','f','g','h',                   'n',         'q','r','t'];
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Show MINST image");
-	    ui.horizontal(|ui| {
-		ui.label("labels file:");
-		ui.label(self.appCtx.labels_file.to_owned());
-	    });
-	    ui.horizontal(|ui| {
-		ui.label("images_file:");
-		ui.label(self.appCtx.images_file.to_owned());
-	    });
-	    ui.horizontal(|ui| {
-		ui.label("select image no:");
-		ui.label(self.appCtx.no_of_image.to_string().to_owned());
-	    });
-	    ui.horizontal(|ui| {
-		ui.label("label of image:");
-		ui.label(self.appCtx.label_of_image.to_string().to_owned());
-		ui.label("(");
-		ui.label(label_table[self.appCtx.label_of_image as usize].to_string());
-		ui.label(")");
-	    });
-
-	    let to_color_image = |image:&Box<[u8]>| -> egui::ColorImage {
-		let data_vec = vec![0; (self.appCtx.row_of_image*self.appCtx.col_of_image*4) as usize];
-		let mut data_boxed:Box<[u8]> = data_vec.into_boxed_slice();
-		for c in 0..self.appCtx.col_of_image {
-		    for r in 0..self.appCtx.row_of_image {
-			let dst_pos = (c*self.appCtx.row_of_image*4+r*4) as usize;
-			let src_pos = (c*self.appCtx.row_of_image+r) as usize;
-			data_boxed[dst_pos+0] = image[src_pos];
-			data_boxed[dst_pos+1] = image[src_pos];
-			data_boxed[dst_pos+2] = image[src_pos];
-			data_boxed[dst_pos+3] = 255;
-		    }
-		}
-		egui::ColorImage::from_rgba_unmultiplied([self.appCtx.row_of_image as usize ,self.appCtx.col_of_image as usize],&data_boxed)
-	    };
-
-	    if let Some(ref image) = self.appCtx.image_data {
-		let minst_image = RetainedImage::from_color_image("minst_image", to_color_image(&image));
-		minst_image.show(ui);
-		//ui.add(egui::Image::new(minst_image.texture_id(ctx),
-		//minst_image.size_vec2()).rotate(90.0_f32.to_radians(), egui::Vec2::splat(0.5)));
+fn export(m:&ArgMatches) {
+    let selection = match (m.value_of("range"), m.value_of("per_class")) {
+	(Some(ranges), _) => match parse_ranges(ranges) {
+	    Ok(ranges) => Selection::Ranges(ranges),
+	    Err(e) => {
+		eprintln!("Error invalid range {}", e);
+		process::exit(2);
 	    }
-	});
+	},
+	(None, Some(count)) => match count.parse::<usize>() {
+	    Ok(count) => Selection::PerClass(count),
+	    Err(e) => {
+		eprintln!("Error invalid per_class {}", e);
+		process::exit(2);
+	    }
+	},
+	(None, None) => unreachable!()
+    };
+    let columns = match m.value_of("columns").unwrap().parse::<usize>() {
+	Ok(columns) => columns,
+	Err(e) => {
+	    eprintln!("Error invalid columns {}", e);
+	    process::exit(2);
+	}
+    };
+
+    let output = m.value_of("output").unwrap();
+    let samples = read_samples(m, None);
+    let result = select(&samples, &selection).and_then(|nos| {
+	if m.contains_id("sprite") {
+	    export_sprite_sheet(&samples, &nos, output, columns)
+	}
+	else {
+	    export_images(&samples, &nos, output)
+	}
+    });
+    if let Err(err) = result {
+	exit_with(&err, err.exit_code());
     }
 }
 
-fn main() {
+fn print_stats(m:&ArgMatches) {
+    let samples = read_samples(m, None);
+    let dataset_stats = stats::compute(&samples);
+    if m.contains_id("json") {
+	println!("{}", serde_json::to_string_pretty(&dataset_stats).unwrap());
+    }
+    else {
+	stats::print_text(&dataset_stats);
+    }
+}
 
-    let appArgs = App::new("mnist_reader")
-	.version("0.1.0")
-	.arg(Arg::with_name("labels_file")
-	     .help("mnist label file")
-	     .short('l')
-	     .long("labels_file")
-	     .takes_value(true)
-	     .required(true))
+fn build(m:&ArgMatches) {
+    let size = match m.value_of("size").unwrap().parse::<usize>() {
+	Ok(size) if size > 0 => size,
+	Ok(_) => {
+	    eprintln!("Error size must be positive");
+	    process::exit(2);
+	},
+	Err(e) => {
+	    eprintln!("Error invalid size {}", e);
+	    process::exit(2);
+	}
+    };
+    let options = BuildOptions {
+	invert: match m.value_of("invert") {
+	    Some("always") => Invert::Always,
+	    Some("never") => Invert::Never,
+	    _ => Invert::Auto
+	},
+	..BuildOptions::for_size(size)
+    };
+
+    let result = class_folders(m.value_of("dir").unwrap()).and_then(|folders| {
+	for folder in folders.iter() {
+	    println!("label {} {} ({} files)", folder.label, folder.name, folder.files.len());
+	}
+	build_dataset(&folders, &options)
+    }).and_then(|(labels, images)| {
+	println!("{} images of {}x{}", labels.shape[0], options.size, options.size);
+	write_idx(m.value_of("labels_file").unwrap(), &labels)?;
+	write_idx(m.value_of("images_file").unwrap(), &images)
+    });
+    if let Err(err) = result {
+	exit_with(&err, err.exit_code());
+    }
+}
+
+fn with_dataset_args(app:App<'static>) -> App<'static> {
+    app.arg(Arg::with_name("labels_file")
+	    .help("mnist label file")
+	    .short('l')
+	    .long("labels_file")
+	    .takes_value(true)
+	    .required(true))
 	.arg(Arg::with_name("images_file")
 	     .help("mnist image file")
 	     .short('i')
 	     .long("images_file")
 	     .takes_value(true)
 	     .required(true))
+	.arg(Arg::with_name("dataset")
+	     .help("dataset of the files. guessed from the name of images file by default")
+	     .long("dataset")
+	     .takes_value(true)
+	     .possible_values(["mnist", "emnist", "fashion", "kmnist"]))
+	.arg(Arg::with_name("emnist")
+	     .help("specified emnist format. same as --dataset emnist")
+	     .long("emnist")
+	     .takes_value(false)
+	     .conflicts_with("dataset"))
+	.arg(Arg::with_name("mapping")
+	     .help("emnist mapping file. emnist-<split>-mapping.txt next to images file by default")
+	     .long("mapping")
+	     .takes_value(true))
+}
+
+// a weight file which can not be used fails before the window is opened
+fn load_network(weight_file:&str) -> Network {
+    match Network::load(weight_file) {
+	Ok(network) => network,
+	Err(err) => {
+	    exit_with(&err, err.exit_code());
+	}
+    }
+}
+
+// the no-th sample is looked up before all samples are read, so that a number out of range fails early
+fn read_samples(m:&ArgMatches, no_of_image:Option<usize>) -> Samples {
+    let labels_file = m.value_of("labels_file").unwrap();
+    let images_file = m.value_of("images_file").unwrap();
+    let kind = match m.value_of("dataset") {
+	Some(name) => DatasetKind::from_name(name).unwrap(),
+	None if m.contains_id("emnist") => DatasetKind::Emnist,
+	None => DatasetKind::detect(images_file)
+    };
+
+    let result = ClassNames::for_dataset(kind, images_file, m.value_of("mapping").map(Path::new))
+	.and_then(|(names, warning)| {
+	    if let Some(warning) = warning {
+		eprintln!("Warning {}", warning);
+	    }
+	    let mut dataset = IdxDataset::open(labels_file, images_file)?;
+	    if let Some(no) = no_of_image {
+		let (label, _) = dataset.get(no)?;
+		println!("label of image {}: {}", no, label);
+	    }
+	    Samples::from_dataset(&mut dataset, kind, names)
+	});
+    match result {
+	Ok(samples) => samples,
+	Err(err) => {
+	    exit_with(&err, err.exit_code());
+	}
+    }
+}
+
+fn main() {
+
+    let appArgs = with_dataset_args(App::new("mnist_reader"))
+	.version("0.1.0")
+	.args_conflicts_with_subcommands(true)
+	.subcommand_negates_reqs(true)
 	.arg(Arg::with_name("no_of_image")
 	     .help("specified no of image")
 	     .short('n')
 	     .long("no_of_image")
 	     .default_value("-1")
 	     .takes_value(true))
-	.arg(Arg::with_name("emnist")
-	     .help("specified emnist format")
-	     .long("emnist")
-	     .takes_value(false));
+	.arg(Arg::with_name("weight_file")
+	     .help("hdf5 or npz weights of simple_mnist_classify to predict images drawn on the canvas")
+	     .short('w')
+	     .long("weight_file")
+	     .takes_value(true))
+	.subcommand(with_dataset_args(App::new("export"))
+		    .about("write images to png files, or a sprite sheet, without window")
+		    .arg(Arg::with_name("output")
+			 .help("output directory, or png file of the sprite sheet")
+			 .short('o')
+			 .long("output")
+			 .takes_value(true)
+			 .required(true))
+		    .arg(Arg::with_name("range")
+			 .help("numbers of images, e.g. 0-99,120")
+			 .long("range")
+			 .takes_value(true))
+		    .arg(Arg::with_name("per_class")
+			 .help("number of images from the beginning of each class")
+			 .long("per_class")
+			 .takes_value(true))
+		    .group(ArgGroup::new("selection")
+			   .args(&["range", "per_class"])
+			   .required(true))
+		    .arg(Arg::with_name("sprite")
+			 .help("write one sprite sheet and csv of labels and positions")
+			 .long("sprite")
+			 .takes_value(false))
+		    .arg(Arg::with_name("columns")
+			 .help("images per row of the sprite sheet")
+			 .long("columns")
+			 .takes_value(true)
+			 .default_value("32")))
+	.subcommand(App::new("build")
+		    .about("build a pair of idx files from png files in folders of each class, e.g. dir/0/*.png, dir/1/*.png")
+		    .arg(Arg::with_name("dir")
+			 .help("directory of class folders. folders named by numbers are the labels, otherwise labels are given in the order of names")
+			 .required(true))
+		    .arg(Arg::with_name("labels_file")
+			 .help("output label file. compressed by gzip when the name ends with .gz")
+			 .short('l')
+			 .long("labels_file")
+			 .takes_value(true)
+			 .required(true))
+		    .arg(Arg::with_name("images_file")
+			 .help("output image file. compressed by gzip when the name ends with .gz")
+			 .short('i')
+			 .long("images_file")
+			 .takes_value(true)
+			 .required(true))
+		    .arg(Arg::with_name("size")
+			 .help("width and height of images")
+			 .long("size")
+			 .takes_value(true)
+			 .default_value("28"))
+		    .arg(Arg::with_name("invert")
+			 .help("make dark ink on light paper white on black like mnist. auto inverts when the border is light")
+			 .long("invert")
+			 .takes_value(true)
+			 .possible_values(["auto", "always", "never"])
+			 .default_value("auto")))
+	.subcommand(with_dataset_args(App::new("stats"))
+		    .about("print class counts, per-pixel mean and variance, intensity histogram and duplicated images")
+		    .arg(Arg::with_name("json")
+			 .help("print as json")
+			 .long("json")
+			 .takes_value(false)));
 
-    let mut ctx:AppContext = match appArgs.try_get_matches() {
+    let (ctx, samples) = match appArgs.try_get_matches() {
 	Ok(m) => {
-	    println!("{:?}",m);
-	    let no_of_image = m.value_of("no_of_image").unwrap().parse::<i32>().unwrap();
+	    if let Some(("export", export_m)) = m.subcommand() {
+		export(export_m);
+		return;
+	    }
+	    if let Some(("build", build_m)) = m.subcommand() {
+		build(build_m);
+		return;
+	    }
+	    if let Some(("stats", stats_m)) = m.subcommand() {
+		print_stats(stats_m);
+		return;
+	    }
+	    // -1 is the default, which shows the first image
+	    let no_of_image = match m.value_of("no_of_image").unwrap().parse::<i32>() {
+		Ok(-1) => 0,
+		Ok(no) if no >= 0 => no as u32,
+		Ok(no) => {
+		    eprintln!("Error invalid no_of_image {}", no);
+		    process::exit(2);
+		},
+		Err(e) => {
+		    eprintln!("Error invalid no_of_image {}", e);
+		    process::exit(2);
+		}
+	    };
 	    let ctx = AppContext {
 		labels_file: String::from(m.value_of("labels_file").unwrap()),
 		images_file: String::from(m.value_of("images_file").unwrap()),
-		no_of_image: if no_of_image == -1 { 0 } else { no_of_image as u32 },
-		row_of_image: 0,
-		col_of_image: 0,
-		label_of_image: 0,
-		image_data: None,
-		is_need_revered: m.contains_id("emnist")
+		no_of_image: no_of_image,
+		weight_file: m.value_of("weight_file").map(String::from)
 	    };
-	    println!("{:?}",ctx.is_need_revered);
-	    ctx
+	    println!("labels {}", ctx.labels_file);
+	    println!("images {}", ctx.images_file);
+	    let samples = read_samples(&m, Some(ctx.no_of_image as usize));
+	    (ctx, samples)
 	},
 	Err(e) => {
-	    println!("Error {}", e);
-	    return;
+	    // prints help and version too
+	    e.exit();
 	}
     };
 
-    println!("labels {}", ctx.labels_file);
-    println!("images {}", ctx.images_file);
-
-    let mut labels_file = BufReader::new(
-	File::open(&ctx.labels_file).expect("Failed to open file")
-    );
-
-    let mut images_file = BufReader::new(
-	File::open(&ctx.images_file).expect("Failed to open file")
-    );
-
-    {
-	let mut header:[u8;8] = unsafe{ mem::MaybeUninit::uninit().assume_init() };
-
-	let _readsize = labels_file.read(&mut header).unwrap_or_else(|err| panic!("IO Error => {}", err));
-	let minst_label_header = minst_label_header::View::new(header);
-	println!("magic {:08x}", minst_label_header.magic().read());
-	println!("num of images {}", minst_label_header.num_of_images().read());
-    }
-
-    {
-	let mut header:[u8;16] = unsafe{ mem::MaybeUninit::uninit().assume_init() };
-
-	let _readsize = images_file.read(&mut header).unwrap_or_else(|err| panic!("IO Error => {}", err));
-	let minst_images_header = minst_image_header::View::new(header);
-	println!("magic {:08x}", minst_images_header.magic().read());
-	println!("num of images {}", minst_images_header.num_of_images().read());
-	println!("num of row {}", minst_images_header.row().read());
-	println!("num of col {}", minst_images_header.col().read());
-
-	ctx.row_of_image = minst_images_header.row().read();
-	ctx.col_of_image = minst_images_header.col().read();
-    }
+    println!("{} images of {}x{}", samples.len(), samples.rows, samples.cols);
 
     println!("no of image: {}", ctx.no_of_image);
+    let no_of_image = ctx.no_of_image as usize;
 
-    {
-	let mut data:[u8;1] = unsafe{ mem::MaybeUninit::uninit().assume_init() };
-	labels_file.seek(SeekFrom::Current(ctx.no_of_image as i64));
-	labels_file.read(&mut data).unwrap_or_else(|err| panic!("IO Error => {}", err));
-	ctx.label_of_image = data[0];
-    }
-
-    {
-	let start_pos = (ctx.row_of_image*ctx.col_of_image*ctx.no_of_image) as i64;
-	let data = vec![0; (ctx.row_of_image*ctx.col_of_image) as usize];
-	let mut data_boxed:Box<[u8]> = data.into_boxed_slice();
-	images_file.seek(SeekFrom::Current(start_pos as i64));
-	images_file.read(&mut data_boxed);
-	ctx.image_data = Some(data_boxed);
-    }
-
-    ctx.image_data = match ctx.image_data {
-	Some(mut image) => {
-	    if ctx. is_need_revered {
-		let data = vec![0; (ctx.row_of_image*ctx.col_of_image) as usize];
-		let mut reversed_image:Box<[u8]> = data.into_boxed_slice();
-		/*
-		for c in 0..ctx.col_of_image as usize {
-		for r in 0..ctx.row_of_image as usize {
-		let pos = (c*(ctx.row_of_image as usize)+r) as usize;
-		print!("{:02x}", image[pos]);
-	    }
-		println!("");
-	    }
-		println!("");
-		 */
-
-		// 右90度回転
-		for c in 0..ctx.col_of_image as usize {
-		    for r in 0..ctx.row_of_image as usize {
-			let src_pos = ((ctx.col_of_image-1) as usize - r)*(ctx.row_of_image as usize)+c;
-			reversed_image[c*(ctx.row_of_image as usize)+r] = image[src_pos];
-		    }
-		}
-		/*
-		for c in 0..ctx.col_of_image as usize {
-		for r in 0..ctx.row_of_image as usize {
-		let pos = (c*(ctx.row_of_image as usize)+r) as usize;
-		print!("{:02x}", reversed_image[pos]);
-	    }
-		println!("");
-	    }
-		println!("");
-		 */
-		// 鏡像反転
-		for c in 0..ctx.col_of_image as usize {
-		    for r in 0..ctx.row_of_image as usize {
-			let src_pos = c*(ctx.row_of_image as usize) + ((ctx.row_of_image-1) as usize - r);
-			image[c*(ctx.row_of_image as usize)+r] = reversed_image[src_pos];
-		    }
-		}
-	    }
-	    Some(image)
-	},
-	None => None
-    };
-
-    if ctx.image_data.is_none() {
-	return
-    }
+    let weight = ctx.weight_file.as_ref().map(|weight_file| (weight_file.clone(), load_network(weight_file)));
 
     let options = eframe::NativeOptions::default();
     eframe::run_native(
         "My egui App",
         options,
-        Box::new(|_cc| Box::new(GuiContext { appCtx: ctx })),
+        Box::new(move |_cc| Box::new(GuiContext::new(&ctx.labels_file, &ctx.images_file, samples, no_of_image, weight))),
     );
 }
@@ -0,0 +1,149 @@
+use std::error;
+use std::fmt;
+use std::path::Path;
+
+use numpy_read::{NpyArray, NpyError, read_npz};
+use numpy_read::hdf5_io::read_hdf5_group;
+
+#[derive(Debug)]
+pub enum ModelError {
+    // the weight file can not be read
+    Read(NpyError),
+    // arrays are missing or do not make the network, or the input does not match it
+    Invalid(String)
+}
+
+impl ModelError {
+    /// Exit status of the command, which does not overlap with errors of idx files.
+    pub fn exit_code(&self) -> i32 {
+	match self {
+	    ModelError::Read(_) => 11,
+	    ModelError::Invalid(_) => 13
+	}
+    }
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    ModelError::Read(err) => write!(f, "{}", err),
+	    ModelError::Invalid(message) => write!(f, "{}", message)
+	}
+    }
+}
+
+impl error::Error for ModelError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+	match self {
+	    ModelError::Read(err) => Some(err),
+	    _ => None
+	}
+    }
+}
+
+impl From<NpyError> for ModelError {
+    fn from(err:NpyError) -> ModelError {
+	ModelError::Read(err)
+    }
+}
+
+struct Layer {
+    // inputs*outputs values in row major order
+    weight: Box<[f32]>,
+    bias: Box<[f32]>,
+    inputs: usize,
+    outputs: usize
+}
+
+/// Three layer network of simple_mnist_classify. Hidden layers are activated by sigmoid,
+/// and the output layer by softmax.
+pub struct Network {
+    layers: Vec<Layer>
+}
+
+impl Network {
+    /// Reads w1, w2, w3 and b1, b2, b3 from a hdf5 file, or a npz file saved by np.savez,
+    /// same as load_weight of simple_mnist_classify.
+    pub fn load<P:AsRef<Path>>(weight_file:P) -> Result<Network,ModelError> {
+	let weight_file = weight_file.as_ref();
+	let arrays = if weight_file.extension().map_or(false, |ext| ext == "npz") {
+	    read_npz(weight_file)?
+	}
+	else {
+	    let mut arrays:Vec<(String, NpyArray)> = Vec::new();
+	    let h = hdf5::File::open(weight_file).map_err(NpyError::from)?;
+	    read_hdf5_group(&h, &mut arrays).map_err(NpyError::from)?;
+	    arrays
+	};
+	Network::from_arrays(&arrays)
+    }
+
+    pub fn from_arrays(arrays:&[(String, NpyArray)]) -> Result<Network,ModelError> {
+	let find = |name:&str| -> Result<&NpyArray,ModelError> {
+	    arrays.iter().find(|(array_name, _)| array_name == name).map(|(_, array)| array)
+		.ok_or_else(|| ModelError::Invalid(format!("{} is not found in the weight file", name)))
+	};
+	let values = |name:&str, array:&NpyArray| -> Result<Box<[f32]>,ModelError> {
+	    array.to_f32().ok_or_else(|| ModelError::Invalid(format!("{} is not real values", name)))
+	};
+
+	let mut layers:Vec<Layer> = Vec::new();
+	for i in 1..=3 {
+	    let (w_name, b_name) = (format!("w{}", i), format!("b{}", i));
+	    let (w, b) = (find(&w_name)?, find(&b_name)?);
+	    if w.shape.len() != 2 || b.len() != w.shape[1] {
+		return Err(ModelError::Invalid(format!("shape {:?} of {} does not match {:?} of {}", w.shape, w_name, b.shape, b_name)));
+	    }
+	    if let Some(previous) = layers.last() {
+		if previous.outputs != w.shape[0] {
+		    return Err(ModelError::Invalid(format!("{} has {} inputs, but the previous layer has {} outputs",
+							 w_name, w.shape[0], previous.outputs)));
+		}
+	    }
+	    layers.push(Layer {
+		weight: values(&w_name, w)?,
+		bias: values(&b_name, b)?,
+		inputs: w.shape[0],
+		outputs: w.shape[1]
+	    });
+	}
+	Ok(Network {
+	    layers: layers
+	})
+    }
+
+    pub fn input_size(&self) -> usize {
+	self.layers[0].inputs
+    }
+
+    pub fn output_size(&self) -> usize {
+	self.layers[self.layers.len()-1].outputs
+    }
+
+    /// Probabilities of each class for an image, whose pixels are scaled to [0, 1]
+    /// as normalized by Tensor::from_mnist.
+    pub fn predict(&self, image:&[u8]) -> Result<Vec<f32>,ModelError> {
+	if image.len() != self.input_size() {
+	    return Err(ModelError::Invalid(format!("{} pixels do not match {} inputs of the network", image.len(), self.input_size())));
+	}
+	let mut x:Vec<f32> = image.iter().map(|&pixel| pixel as f32/255.0).collect();
+	for (i, layer) in self.layers.iter().enumerate() {
+	    let mut a = layer.bias.to_vec();
+	    for (row, &input) in x.iter().enumerate() {
+		let weight = &layer.weight[row*layer.outputs..(row+1)*layer.outputs];
+		a.iter_mut().zip(weight.iter()).for_each(|(a, w)| *a += input*w);
+	    }
+	    x = if i < self.layers.len()-1 {
+		a.iter().map(|&a| 1.0/(1.0 + (-a).exp())).collect()
+	    }
+	    else {
+		// shifted by the maximum against overflow
+		let max = a.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+		let exp:Vec<f32> = a.iter().map(|&a| (a-max).exp()).collect();
+		let sum:f32 = exp.iter().sum();
+		exp.iter().map(|e| e/sum).collect()
+	    };
+	}
+	Ok(x)
+    }
+}
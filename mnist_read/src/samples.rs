@@ -0,0 +1,74 @@
+use std::path::Path;
+
+use std::io::prelude::*;
+
+use crate::{IdxDataset, IdxError};
+use crate::classes::{ClassNames, DatasetKind};
+use crate::emnist;
+
+/// Labels and images read from idx files.
+pub struct Samples {
+    pub labels: Box<[u8]>,
+    // images of rows*cols pixels one after another
+    pub images: Box<[u8]>,
+    pub rows: usize,
+    pub cols: usize,
+    pub kind: DatasetKind,
+    pub names: ClassNames
+}
+
+impl Samples {
+    /// Reads a pair of label and image files. Labels must be 1 dimensional u8,
+    /// and images must be 3 dimensional u8 of the same count.
+    pub fn read<P:AsRef<Path>>(labels_file:P, images_file:P, kind:DatasetKind, names:ClassNames) -> Result<Samples,IdxError> {
+	Samples::from_dataset(&mut IdxDataset::open(labels_file, images_file)?, kind, names)
+    }
+
+    /// Reads every sample of the dataset into memory.
+    pub fn from_dataset<R:Read+Seek>(dataset:&mut IdxDataset<R>, kind:DatasetKind, names:ClassNames) -> Result<Samples,IdxError> {
+	let (labels, images) = dataset.read_all()?;
+	Ok(Samples {
+	    labels: labels,
+	    images: images,
+	    rows: dataset.rows(),
+	    cols: dataset.cols(),
+	    kind: kind,
+	    names: names
+	})
+    }
+
+    pub fn len(&self) -> usize {
+	self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.labels.is_empty()
+    }
+
+    /// Label followed by the class name, e.g. "10 (A)".
+    pub fn label_text(&self, label:u8) -> String {
+	match self.names.name(label) {
+	    Some(name) => format!("{} ({})", label, name),
+	    None => label.to_string()
+	}
+    }
+
+    /// Labels found in the samples in ascending order.
+    pub fn classes(&self) -> Vec<u8> {
+	let mut found = [false;256];
+	self.labels.iter().for_each(|&label| found[label as usize] = true);
+	(0..=255u8).filter(|&label| found[label as usize]).collect()
+    }
+
+    /// Pixels of the no-th image as shown, that is upright for EMNIST.
+    pub fn image(&self, no:usize) -> Box<[u8]> {
+	let image_size = self.rows*self.cols;
+	let image = &self.images[no*image_size..(no+1)*image_size];
+	if self.kind.is_transposed() {
+	    emnist::to_upright(image, self.rows, self.cols)
+	}
+	else {
+	    image.to_vec().into_boxed_slice()
+	}
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use serde::Serialize;
+
+use crate::Samples;
+
+#[derive(Debug,Clone,Serialize)]
+pub struct ClassCount {
+    pub label: u8,
+    pub name: Option<String>,
+    pub count: usize
+}
+
+/// Statistics to sanity-check a dataset before training.
+#[derive(Debug,Clone,Serialize)]
+pub struct DatasetStats {
+    pub count: usize,
+    pub rows: usize,
+    pub cols: usize,
+    pub classes: Vec<ClassCount>,
+    // per-pixel mean and population variance, rows*cols values in the stored layout
+    pub mean: Vec<f64>,
+    pub variance: Vec<f64>,
+    // histogram[v] is the number of pixels of the intensity v
+    pub histogram: Vec<usize>,
+    // numbers of images whose raw pixels are identical, in ascending order
+    pub duplicates: Vec<Vec<usize>>
+}
+
+impl DatasetStats {
+    /// Number of images which are the same as an earlier image.
+    pub fn duplicate_count(&self) -> usize {
+	self.duplicates.iter().map(|group| group.len()-1).sum()
+    }
+}
+
+pub fn compute(samples:&Samples) -> DatasetStats {
+    let size = samples.rows*samples.cols;
+
+    let mut counts = [0usize; 256];
+    for &label in samples.labels.iter() {
+	counts[label as usize] += 1;
+    }
+    let classes = samples.classes().into_iter().map(|label| {
+	ClassCount {
+	    label: label,
+	    name: samples.names.name(label).map(String::from),
+	    count: counts[label as usize]
+	}
+    }).collect();
+
+    let mut sum = vec![0.0; size];
+    let mut square_sum = vec![0.0; size];
+    let mut histogram = vec![0usize; 256];
+    for image in samples.images.chunks(size) {
+	for (pos, &pixel) in image.iter().enumerate() {
+	    let value = pixel as f64;
+	    sum[pos] += value;
+	    square_sum[pos] += value*value;
+	    histogram[pixel as usize] += 1;
+	}
+    }
+    let n = samples.len() as f64;
+    let mean:Vec<f64> = sum.iter().map(|s| if samples.is_empty() { 0.0 } else { s/n }).collect();
+    let variance = square_sum.iter().zip(mean.iter()).map(|(s, m)| {
+	if samples.is_empty() { 0.0 } else { (s/n - m*m).max(0.0) }
+    }).collect();
+
+    DatasetStats {
+	count: samples.len(),
+	rows: samples.rows,
+	cols: samples.cols,
+	classes: classes,
+	mean: mean,
+	variance: variance,
+	histogram: histogram,
+	duplicates: find_duplicates(&samples.images, size)
+    }
+}
+
+/// Groups images of identical bytes. Images are bucketed by the hash of the raw buffer,
+/// and compared byte by byte in the bucket so that hash collisions are not reported.
+pub fn find_duplicates(images:&[u8], size:usize) -> Vec<Vec<usize>> {
+    if size == 0 {
+	return Vec::new();
+    }
+    let mut buckets:HashMap<u64, Vec<usize>> = HashMap::new();
+    for (no, image) in images.chunks(size).enumerate() {
+	let mut hasher = DefaultHasher::new();
+	image.hash(&mut hasher);
+	buckets.entry(hasher.finish()).or_insert_with(Vec::new).push(no);
+    }
+
+    let image = |no:usize| &images[no*size..(no+1)*size];
+    let mut duplicates:Vec<Vec<usize>> = Vec::new();
+    for (_, nos) in buckets.into_iter().filter(|(_, nos)| nos.len() > 1) {
+	let mut groups:Vec<Vec<usize>> = Vec::new();
+	for no in nos {
+	    match groups.iter_mut().find(|group| image(group[0]) == image(no)) {
+		Some(group) => group.push(no),
+		None => groups.push(vec![no])
+	    }
+	}
+	duplicates.extend(groups.into_iter().filter(|group| group.len() > 1));
+    }
+    duplicates.sort();
+    duplicates
+}
+
+pub fn print_text(stats:&DatasetStats) {
+    println!("{} images of {}x{}", stats.count, stats.rows, stats.cols);
+    println!("classes");
+    for class in stats.classes.iter() {
+	let ratio = if stats.count == 0 { 0.0 } else { class.count as f64*100.0/stats.count as f64 };
+	match class.name {
+	    Some(ref name) => println!("  {:3} {:12} {:8} {:6.2}%", class.label, name, class.count, ratio),
+	    None => println!("  {:3} {:8} {:6.2}%", class.label, class.count, ratio)
+	}
+    }
+
+    let range = |values:&[f64]| {
+	values.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &v| (min.min(v), max.max(v)))
+    };
+    let (mean_min, mean_max) = range(&stats.mean);
+    let (variance_min, variance_max) = range(&stats.variance);
+    println!("per-pixel mean min {:.3} max {:.3}", mean_min, mean_max);
+    println!("per-pixel variance min {:.3} max {:.3}", variance_min, variance_max);
+
+    // 256 levels are summed up by 16 in text
+    println!("intensity histogram");
+    for (i, counts) in stats.histogram.chunks(16).enumerate() {
+	println!("  [{:3}, {:3}] {}", i*16, i*16+15, counts.iter().sum::<usize>());
+    }
+
+    println!("{} duplicated images in {} groups", stats.duplicate_count(), stats.duplicates.len());
+    for group in stats.duplicates.iter() {
+	println!("  {:?}", group);
+    }
+}
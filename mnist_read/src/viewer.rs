@@ -0,0 +1,580 @@
+use std::cmp;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use eframe::egui;
+use eframe::egui::plot::{Bar, BarChart, Plot};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use egui_extras::RetainedImage;
+
+use mnist_read::{IdxError, Samples, write_idx};
+use mnist_read::augment::{self, Augmentation};
+use mnist_read::emnist;
+use mnist_read::model::Network;
+
+use crate::canvas::Canvas;
+use mnist_read::stats::{self, DatasetStats};
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+enum ViewMode {
+    Single,
+    Grid,
+    Stats,
+    Augment,
+    Draw
+}
+
+pub struct GuiContext {
+    labels_file: String,
+    images_file: String,
+    // shared with the export thread
+    samples: Arc<Samples>,
+    classes: Vec<u8>,
+    mode: ViewMode,
+    no_of_image: usize,
+    jump_to: String,
+    zoom: f32,
+    // the image of shown_no is kept until the selection changes
+    shown_no: Option<usize>,
+    image: Option<RetainedImage>,
+    grid_rows: usize,
+    grid_cols: usize,
+    grid_page: usize,
+    // only samples of the label are shown in the grid
+    filter: Option<u8>,
+    filtered: Vec<usize>,
+    // thumbnails are kept until the filter, page or grid size changes
+    thumbnails_key: Option<(Option<u8>, usize, usize, usize)>,
+    thumbnails: Vec<(usize, RetainedImage)>,
+    // statistics are computed when the view is opened first
+    stats: Option<DatasetStats>,
+    stats_images: Vec<(String, RetainedImage)>,
+    augmentation: Augmentation,
+    // the same seed gives the same augmented image until resampled
+    augment_seed: u64,
+    augmented_key: Option<(usize, Augmentation, u64)>,
+    augmented: Vec<RetainedImage>,
+    export_labels: String,
+    export_images: String,
+    export_copies: usize,
+    export_with_original: bool,
+    export_status: String,
+    // the export runs on a thread, which counts the samples done in export_progress
+    export_job: Option<thread::JoinHandle<Result<usize,IdxError>>>,
+    export_progress: Arc<AtomicUsize>,
+    canvas: Canvas,
+    weight_file: String,
+    network: Option<Network>,
+    network_status: String,
+    // prediction is kept until the canvas or the network changes
+    predicted_key: Option<(usize, usize)>,
+    network_version: usize,
+    drawn: Option<RetainedImage>,
+    probabilities: Option<Vec<f32>>
+}
+
+impl GuiContext {
+    pub fn new(labels_file:&str, images_file:&str, samples:Samples, no_of_image:usize,
+	       weight:Option<(String, Network)>) -> GuiContext {
+	let classes = samples.classes();
+	let filtered = (0..samples.len()).collect();
+	let (weight_file, network) = match weight {
+	    Some((weight_file, network)) => (weight_file, Some(network)),
+	    None => (String::new(), None)
+	};
+	GuiContext {
+	    labels_file: labels_file.to_string(),
+	    images_file: images_file.to_string(),
+	    samples: Arc::new(samples),
+	    classes: classes,
+	    mode: ViewMode::Single,
+	    no_of_image: no_of_image,
+	    jump_to: no_of_image.to_string(),
+	    zoom: 4.0,
+	    shown_no: None,
+	    image: None,
+	    grid_rows: 8,
+	    grid_cols: 8,
+	    grid_page: 0,
+	    filter: None,
+	    filtered: filtered,
+	    thumbnails_key: None,
+	    thumbnails: Vec::new(),
+	    stats: None,
+	    stats_images: Vec::new(),
+	    augmentation: Augmentation::default(),
+	    augment_seed: 0,
+	    augmented_key: None,
+	    augmented: Vec::new(),
+	    export_labels: "augmented-labels-idx1-ubyte".to_string(),
+	    export_images: "augmented-images-idx3-ubyte".to_string(),
+	    export_copies: 1,
+	    export_with_original: false,
+	    export_status: String::new(),
+	    export_job: None,
+	    export_progress: Arc::new(AtomicUsize::new(0)),
+	    canvas: Canvas::new(),
+	    weight_file: weight_file,
+	    network: network,
+	    network_status: String::new(),
+	    predicted_key: None,
+	    network_version: 0,
+	    drawn: None,
+	    probabilities: None
+	}
+    }
+
+    fn select(&mut self, no:usize) {
+	self.no_of_image = cmp::min(no, self.samples.len()-1);
+	self.jump_to = self.no_of_image.to_string();
+    }
+
+    fn set_filter(&mut self, filter:Option<u8>) {
+	self.filter = filter;
+	self.filtered = match filter {
+	    Some(label) => (0..self.samples.len()).filter(|&no| self.samples.labels[no] == label).collect(),
+	    None => (0..self.samples.len()).collect()
+	};
+	self.grid_page = 0;
+    }
+
+    fn grid_pages(&self) -> usize {
+	let page_size = self.grid_rows*self.grid_cols;
+	cmp::max((self.filtered.len() + page_size - 1)/page_size, 1)
+    }
+
+    fn select_page(&mut self, page:usize) {
+	self.grid_page = cmp::min(page, self.grid_pages()-1);
+    }
+
+    // left and right move by one image, page up and down by ten, home and end to the first and last.
+    // in the grid, they move by one page.
+    fn handle_keys(&mut self, ctx:&egui::Context) {
+	let (no, step) = match self.mode {
+	    ViewMode::Single | ViewMode::Augment => (self.no_of_image, 10),
+	    ViewMode::Grid => (self.grid_page, 1),
+	    ViewMode::Stats | ViewMode::Draw => return
+	};
+	let selected = {
+	    let input = ctx.input();
+	    if input.key_pressed(egui::Key::ArrowRight) { Some(no.saturating_add(1)) }
+	    else if input.key_pressed(egui::Key::ArrowLeft) { Some(no.saturating_sub(1)) }
+	    else if input.key_pressed(egui::Key::PageDown) { Some(no.saturating_add(step)) }
+	    else if input.key_pressed(egui::Key::PageUp) { Some(no.saturating_sub(step)) }
+	    else if input.key_pressed(egui::Key::Home) { Some(0) }
+	    else if input.key_pressed(egui::Key::End) { Some(usize::MAX) }
+	    else { None }
+	};
+	match (self.mode, selected) {
+	    (ViewMode::Single, Some(selected)) | (ViewMode::Augment, Some(selected)) => self.select(selected),
+	    (ViewMode::Grid, Some(selected)) => self.select_page(selected),
+	    (_, _) => ()
+	}
+    }
+
+    fn to_color_image(&self, image:&[u8]) -> egui::ColorImage {
+	let mut data_boxed:Box<[u8]> = vec![0; self.samples.rows*self.samples.cols*4].into_boxed_slice();
+	for (pos, &pixel) in image.iter().enumerate() {
+	    data_boxed[pos*4] = pixel;
+	    data_boxed[pos*4+1] = pixel;
+	    data_boxed[pos*4+2] = pixel;
+	    data_boxed[pos*4+3] = 255;
+	}
+	egui::ColorImage::from_rgba_unmultiplied([self.samples.cols, self.samples.rows], &data_boxed)
+    }
+
+    fn show_navigation(&mut self, ui:&mut egui::Ui) {
+	ui.horizontal(|ui| {
+	    if ui.button("<").clicked() {
+		self.select(self.no_of_image.saturating_sub(1));
+	    }
+	    if ui.button(">").clicked() {
+		self.select(self.no_of_image.saturating_add(1));
+	    }
+	    let mut no = self.no_of_image;
+	    if ui.add(egui::Slider::new(&mut no, 0..=self.samples.len()-1).text("no")).changed() {
+		self.select(no);
+	    }
+	    ui.label("jump to:");
+	    let response = ui.add(egui::TextEdit::singleline(&mut self.jump_to).desired_width(80.0));
+	    if response.lost_focus() && ui.input().key_pressed(egui::Key::Enter) {
+		match self.jump_to.trim().parse::<usize>() {
+		    Ok(no) => self.select(no),
+		    Err(_) => self.jump_to = self.no_of_image.to_string()
+		}
+	    }
+	});
+    }
+
+    fn show_single(&mut self, ui:&mut egui::Ui) {
+	self.show_navigation(ui);
+	ui.add(egui::Slider::new(&mut self.zoom, 1.0..=16.0).text("zoom"));
+	ui.horizontal(|ui| {
+	    ui.label("label of image:");
+	    ui.label(self.samples.label_text(self.samples.labels[self.no_of_image]));
+	});
+
+	if self.shown_no != Some(self.no_of_image) {
+	    let color_image = self.to_color_image(&self.samples.image(self.no_of_image));
+	    self.image = Some(RetainedImage::from_color_image("minst_image", color_image));
+	    self.shown_no = Some(self.no_of_image);
+	}
+	if let Some(ref image) = self.image {
+	    image.show_scaled(ui, self.zoom);
+	}
+    }
+
+    fn show_grid(&mut self, ui:&mut egui::Ui) {
+	ui.horizontal(|ui| {
+	    let mut filter = self.filter;
+	    egui::ComboBox::from_label("class")
+		.selected_text(filter.map_or("all".to_string(), |label| self.samples.label_text(label)))
+		.show_ui(ui, |ui| {
+		    ui.selectable_value(&mut filter, None, "all");
+		    for &label in self.classes.iter() {
+			ui.selectable_value(&mut filter, Some(label), self.samples.label_text(label));
+		    }
+		});
+	    if filter != self.filter {
+		self.set_filter(filter);
+	    }
+	    ui.add(egui::Slider::new(&mut self.grid_rows, 1..=16).text("rows"));
+	    ui.add(egui::Slider::new(&mut self.grid_cols, 1..=16).text("cols"));
+	});
+	self.select_page(self.grid_page);
+	ui.horizontal(|ui| {
+	    if ui.button("<").clicked() {
+		self.select_page(self.grid_page.saturating_sub(1));
+	    }
+	    if ui.button(">").clicked() {
+		self.select_page(self.grid_page.saturating_add(1));
+	    }
+	    ui.label(format!("page {} / {}, {} images", self.grid_page+1, self.grid_pages(), self.filtered.len()));
+	});
+
+	let key = (self.filter, self.grid_page, self.grid_rows, self.grid_cols);
+	if self.thumbnails_key != Some(key) {
+	    let page_size = self.grid_rows*self.grid_cols;
+	    let start = self.grid_page*page_size;
+	    let end = cmp::min(start+page_size, self.filtered.len());
+	    self.thumbnails = self.filtered[start..end].iter().map(|&no| {
+		let color_image = self.to_color_image(&self.samples.image(no));
+		(no, RetainedImage::from_color_image(format!("thumbnail {}", no), color_image))
+	    }).collect();
+	    self.thumbnails_key = Some(key);
+	}
+
+	// a clicked thumbnail is enlarged in the single view
+	let mut clicked:Option<usize> = None;
+	egui::ScrollArea::both().show(ui, |ui| {
+	    egui::Grid::new("thumbnails").show(ui, |ui| {
+		for (i, (no, thumbnail)) in self.thumbnails.iter().enumerate() {
+		    ui.vertical(|ui| {
+			let button = egui::ImageButton::new(thumbnail.texture_id(ui.ctx()), thumbnail.size_vec2()*2.0);
+			if ui.add(button).on_hover_text(format!("no {}", no)).clicked() {
+			    clicked = Some(*no);
+			}
+			ui.label(self.samples.label_text(self.samples.labels[*no]));
+		    });
+		    if (i+1) % self.grid_cols == 0 {
+			ui.end_row();
+		    }
+		}
+	    });
+	});
+	if let Some(no) = clicked {
+	    self.select(no);
+	    self.mode = ViewMode::Single;
+	}
+    }
+
+    // values in the stored layout are scaled so that the maximum is white, and shown upright
+    fn to_scaled_image(&self, values:&[f64]) -> egui::ColorImage {
+	let max = values.iter().cloned().fold(0.0, f64::max);
+	let pixels:Vec<u8> = values.iter().map(|&v| if max > 0.0 { (v/max*255.0).round() as u8 } else { 0 }).collect();
+	if self.samples.kind.is_transposed() {
+	    self.to_color_image(&emnist::to_upright(&pixels, self.samples.rows, self.samples.cols))
+	}
+	else {
+	    self.to_color_image(&pixels)
+	}
+    }
+
+    fn show_stats(&mut self, ui:&mut egui::Ui) {
+	if self.stats.is_none() {
+	    let dataset_stats = stats::compute(&self.samples);
+	    self.stats_images = vec![
+		("mean".to_string(), RetainedImage::from_color_image("mean", self.to_scaled_image(&dataset_stats.mean))),
+		("variance".to_string(), RetainedImage::from_color_image("variance", self.to_scaled_image(&dataset_stats.variance)))
+	    ];
+	    self.stats = Some(dataset_stats);
+	}
+	let dataset_stats = self.stats.as_ref().unwrap();
+
+	let mut clicked:Option<usize> = None;
+	egui::ScrollArea::vertical().show(ui, |ui| {
+	    ui.label(format!("{} images of {}x{}", dataset_stats.count, dataset_stats.rows, dataset_stats.cols));
+	    ui.heading("classes");
+	    egui::Grid::new("class_counts").show(ui, |ui| {
+		for class in dataset_stats.classes.iter() {
+		    ui.label(self.samples.label_text(class.label));
+		    ui.label(class.count.to_string());
+		    ui.end_row();
+		}
+	    });
+
+	    ui.heading("per-pixel mean and variance");
+	    ui.horizontal(|ui| {
+		for (name, image) in self.stats_images.iter() {
+		    ui.vertical(|ui| {
+			ui.label(name.to_owned());
+			image.show_scaled(ui, 4.0);
+		    });
+		}
+	    });
+
+	    ui.heading("intensity histogram");
+	    let bars = dataset_stats.histogram.iter().enumerate().map(|(v, &count)| Bar::new(v as f64, count as f64)).collect();
+	    Plot::new("histogram").height(200.0).show(ui, |plot_ui| plot_ui.bar_chart(BarChart::new(bars)));
+
+	    ui.heading(format!("{} duplicated images in {} groups", dataset_stats.duplicate_count(), dataset_stats.duplicates.len()));
+	    // a clicked number is shown in the single view
+	    for group in dataset_stats.duplicates.iter() {
+		ui.horizontal(|ui| {
+		    for &no in group.iter() {
+			if ui.button(no.to_string()).clicked() {
+			    clicked = Some(no);
+			}
+		    }
+		});
+	    }
+	});
+	if let Some(no) = clicked {
+	    self.select(no);
+	    self.mode = ViewMode::Single;
+	}
+    }
+
+    fn show_augment(&mut self, ui:&mut egui::Ui) {
+	self.show_navigation(ui);
+	egui::Grid::new("augmentation").show(ui, |ui| {
+	    ui.add(egui::Slider::new(&mut self.augmentation.rotation, 0.0..=180.0).text("rotation (degrees)"));
+	    ui.add(egui::Slider::new(&mut self.augmentation.shift, 0.0..=8.0).text("shift (pixels)"));
+	    ui.end_row();
+	    ui.add(egui::Slider::new(&mut self.augmentation.scale, 0.0..=0.5).text("scale"));
+	    ui.add(egui::Slider::new(&mut self.augmentation.noise, 0.0..=64.0).text("noise"));
+	    ui.end_row();
+	    ui.add(egui::Slider::new(&mut self.augmentation.elastic_alpha, 0.0..=40.0).text("elastic alpha"));
+	    ui.add(egui::Slider::new(&mut self.augmentation.elastic_sigma, 1.0..=8.0).text("elastic sigma"));
+	    ui.end_row();
+	    ui.add(egui::Slider::new(&mut self.augmentation.invert, 0.0..=1.0).text("invert probability"));
+	    ui.end_row();
+	});
+	ui.horizontal(|ui| {
+	    if ui.button("resample").clicked() {
+		self.augment_seed = self.augment_seed.wrapping_add(1);
+	    }
+	    if ui.button("reset").clicked() {
+		self.augmentation = Augmentation::default();
+	    }
+	    ui.add(egui::Slider::new(&mut self.zoom, 1.0..=16.0).text("zoom"));
+	});
+
+	let key = (self.no_of_image, self.augmentation.clone(), self.augment_seed);
+	if self.augmented_key.as_ref() != Some(&key) {
+	    let original = self.samples.image(self.no_of_image);
+	    let mut rng = XorShiftRng::seed_from_u64(self.augment_seed);
+	    let augmented = augment::augment(&original, self.samples.rows, self.samples.cols, &self.augmentation, &mut rng);
+	    self.augmented = vec![
+		RetainedImage::from_color_image("original", self.to_color_image(&original)),
+		RetainedImage::from_color_image("augmented", self.to_color_image(&augmented))
+	    ];
+	    self.augmented_key = Some(key);
+	}
+	ui.label(format!("label of image: {}", self.samples.label_text(self.samples.labels[self.no_of_image])));
+	ui.horizontal(|ui| {
+	    for (name, image) in ["original", "augmented"].iter().zip(self.augmented.iter()) {
+		ui.vertical(|ui| {
+		    ui.label(*name);
+		    image.show_scaled(ui, self.zoom);
+		});
+	    }
+	});
+
+	ui.separator();
+	ui.horizontal(|ui| {
+	    ui.label("labels file:");
+	    ui.add(egui::TextEdit::singleline(&mut self.export_labels).desired_width(240.0));
+	    ui.label("images file:");
+	    ui.add(egui::TextEdit::singleline(&mut self.export_images).desired_width(240.0));
+	});
+	ui.horizontal(|ui| {
+	    ui.add(egui::Slider::new(&mut self.export_copies, 1..=16).text("copies"));
+	    ui.checkbox(&mut self.export_with_original, "with original");
+	    if self.export_job.is_none() && ui.button("export").clicked() {
+		self.start_export();
+	    }
+	    ui.label(self.export_status.to_owned());
+	});
+    }
+
+    fn start_export(&mut self) {
+	let samples = Arc::clone(&self.samples);
+	let progress = Arc::clone(&self.export_progress);
+	// every sample is augmented with the preview seed, so the export is reproducible
+	let (augmentation, copies, with_original, seed) = (self.augmentation.clone(), self.export_copies,
+							   self.export_with_original, self.augment_seed);
+	let (labels_file, images_file) = (self.export_labels.clone(), self.export_images.clone());
+	progress.store(0, Ordering::Relaxed);
+	self.export_job = Some(thread::spawn(move || {
+	    let (labels, images) = augment::augment_samples(&samples, &augmentation, copies, with_original, seed,
+							    |done| progress.store(done, Ordering::Relaxed))?;
+	    write_idx(&labels_file, &labels)?;
+	    write_idx(&images_file, &images)?;
+	    Ok(labels.shape[0])
+	}));
+    }
+
+    // the status is updated every frame until the export thread finishes
+    fn poll_export(&mut self, ctx:&egui::Context) {
+	let finished = match self.export_job {
+	    Some(ref job) => job.is_finished(),
+	    None => {
+		return;
+	    }
+	};
+	if finished {
+	    self.export_status = match self.export_job.take().unwrap().join() {
+		Ok(Ok(count)) => format!("{} images are written", count),
+		Ok(Err(err)) => format!("Error {}", err),
+		Err(_) => "Error export thread panicked".to_string()
+	    };
+	}
+	else {
+	    let done = self.export_progress.load(Ordering::Relaxed);
+	    self.export_status = if done < self.samples.len() {
+		format!("augmenting {}/{} samples", done, self.samples.len())
+	    }
+	    else {
+		"writing files".to_string()
+	    };
+	    ctx.request_repaint();
+	}
+    }
+
+    fn show_draw(&mut self, ui:&mut egui::Ui) {
+	ui.horizontal(|ui| {
+	    ui.label("weight file:");
+	    ui.add(egui::TextEdit::singleline(&mut self.weight_file).desired_width(320.0));
+	    if ui.button("load").clicked() {
+		match Network::load(&self.weight_file) {
+		    Ok(network) => {
+			self.network_status = format!("{} inputs, {} classes", network.input_size(), network.output_size());
+			self.network = Some(network);
+		    },
+		    Err(err) => {
+			self.network_status = format!("Error {}", err);
+			self.network = None;
+		    }
+		}
+		self.network_version += 1;
+	    }
+	    ui.label(self.network_status.to_owned());
+	});
+	if self.samples.rows != self.samples.cols {
+	    ui.label(format!("drawing needs square images, but images are {}x{}", self.samples.rows, self.samples.cols));
+	    return;
+	}
+
+	ui.horizontal(|ui| {
+	    self.canvas.show(ui, 280.0);
+	    ui.vertical(|ui| {
+		if ui.button("clear").clicked() {
+		    self.canvas.clear();
+		}
+		ui.add(egui::Slider::new(&mut self.canvas.pen_width, 0.02..=0.2).text("pen width"));
+		ui.label(format!("{}x{} image", self.samples.cols, self.samples.rows));
+		if let Some(ref drawn) = self.drawn {
+		    drawn.show_scaled(ui, 4.0);
+		}
+	    });
+	});
+
+	let key = (self.canvas.version, self.network_version);
+	if self.predicted_key != Some(key) {
+	    let image = self.canvas.to_image(self.samples.rows);
+	    // the network takes images in the stored layout, which is transposed for EMNIST
+	    let input = if self.samples.kind.is_transposed() {
+		emnist::to_upright(&image, self.samples.rows, self.samples.cols)
+	    }
+	    else {
+		image.clone()
+	    };
+	    self.probabilities = match self.network {
+		Some(ref network) if !self.canvas.is_empty() => match network.predict(&input) {
+		    Ok(probabilities) => Some(probabilities),
+		    Err(err) => {
+			self.network_status = format!("Error {}", err);
+			None
+		    }
+		},
+		_ => None
+	    };
+	    self.drawn = Some(RetainedImage::from_color_image("drawn", self.to_color_image(&image)));
+	    self.predicted_key = Some(key);
+	}
+
+	if let Some(ref probabilities) = self.probabilities {
+	    let (label, probability) = probabilities.iter().enumerate()
+		.fold((0, 0.0), |best, (label, &p)| if p > best.1 { (label, p) } else { best });
+	    ui.heading(format!("prediction: {} {:.1}%", self.samples.label_text(label as u8), probability*100.0));
+	    let bars = probabilities.iter().enumerate().map(|(label, &p)| {
+		Bar::new(label as f64, p as f64).name(self.samples.label_text(label as u8))
+	    }).collect();
+	    Plot::new("probabilities").height(200.0).include_y(1.0)
+		.show(ui, |plot_ui| plot_ui.bar_chart(BarChart::new(bars)));
+	}
+	else if self.network.is_none() {
+	    ui.label("load a weight file to predict the drawn image");
+	}
+    }
+}
+
+impl eframe::App for GuiContext {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+	// keys are for the text box while it has focus
+	if !ctx.wants_keyboard_input() {
+	    self.handle_keys(ctx);
+	}
+	self.poll_export(ctx);
+
+	egui::CentralPanel::default().show(ctx, |ui| {
+	    ui.heading("Show MINST image");
+	    ui.horizontal(|ui| {
+		ui.label("labels file:");
+		ui.label(self.labels_file.to_owned());
+	    });
+	    ui.horizontal(|ui| {
+		ui.label("images_file:");
+		ui.label(self.images_file.to_owned());
+	    });
+	    ui.horizontal(|ui| {
+		ui.selectable_value(&mut self.mode, ViewMode::Single, "single");
+		ui.selectable_value(&mut self.mode, ViewMode::Grid, "grid");
+		ui.selectable_value(&mut self.mode, ViewMode::Stats, "stats");
+		ui.selectable_value(&mut self.mode, ViewMode::Augment, "augment");
+		ui.selectable_value(&mut self.mode, ViewMode::Draw, "draw");
+	    });
+	    ui.separator();
+	    match self.mode {
+		ViewMode::Single => self.show_single(ui),
+		ViewMode::Grid => self.show_grid(ui),
+		ViewMode::Stats => self.show_stats(ui),
+		ViewMode::Augment => self.show_augment(ui),
+		ViewMode::Draw => self.show_draw(ui)
+	    }
+	});
+    }
+}
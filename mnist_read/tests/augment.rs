@@ -0,0 +1,95 @@
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use mnist_read::{IdxData, Samples};
+use mnist_read::augment::{Augmentation, augment, augment_samples};
+use mnist_read::classes::{ClassNames, DatasetKind};
+
+const SIZE:usize = 9;
+
+// a 9x9 image with one pixel of 255 at (row, col)
+fn dot(row:usize, col:usize) -> Vec<u8> {
+    let mut image = vec![0; SIZE*SIZE];
+    image[row*SIZE + col] = 255;
+    image
+}
+
+// positions of non zero pixels relative to the centre
+fn ink(image:&[u8]) -> Vec<(f64, f64)> {
+    let centre = (SIZE/2) as f64;
+    (0..SIZE*SIZE).filter(|&pos| image[pos] > 0)
+	.map(|pos| ((pos % SIZE) as f64 - centre, (pos / SIZE) as f64 - centre)).collect()
+}
+
+#[test]
+fn identity_at_zero_strength() {
+    let image:Vec<u8> = (0..SIZE*SIZE).map(|i| (i*3) as u8).collect();
+    for seed in 0..8 {
+	let mut rng = XorShiftRng::seed_from_u64(seed);
+	assert_eq!(&augment(&image, SIZE, SIZE, &Augmentation::default(), &mut rng)[..], &image[..]);
+    }
+}
+
+#[test]
+fn same_seed_same_image() {
+    let augmentation = Augmentation { rotation: 30.0, shift: 2.0, noise: 8.0, ..Augmentation::default() };
+    let image = dot(2, 6);
+    let first = augment(&image, SIZE, SIZE, &augmentation, &mut XorShiftRng::seed_from_u64(7));
+    let second = augment(&image, SIZE, SIZE, &augmentation, &mut XorShiftRng::seed_from_u64(7));
+    assert_eq!(first, second);
+}
+
+#[test]
+fn rotation_keeps_distance() {
+    let augmentation = Augmentation { rotation: 90.0, ..Augmentation::default() };
+    for seed in 0..16 {
+	let mut rng = XorShiftRng::seed_from_u64(seed);
+	// the centre is fixed
+	assert_eq!(augment(&dot(4, 4), SIZE, SIZE, &augmentation, &mut rng)[4*SIZE + 4], 255);
+	// a dot 2 pixels right of the centre stays on the circle, blurred by bilinear interpolation
+	let rotated = augment(&dot(4, 6), SIZE, SIZE, &augmentation, &mut rng);
+	let ink = ink(&rotated);
+	assert!(!ink.is_empty());
+	for (x, y) in ink {
+	    let distance = (x*x + y*y).sqrt();
+	    assert!((0.5..=3.5).contains(&distance), "seed {} ({}, {})", seed, x, y);
+	}
+    }
+}
+
+#[test]
+fn shift_is_bounded() {
+    let augmentation = Augmentation { shift: 2.0, ..Augmentation::default() };
+    for seed in 0..16 {
+	let mut rng = XorShiftRng::seed_from_u64(seed);
+	let shifted = augment(&dot(4, 4), SIZE, SIZE, &augmentation, &mut rng);
+	let ink = ink(&shifted);
+	assert!(!ink.is_empty());
+	// at most 2 pixels, and 1 more pixel of interpolation
+	for (x, y) in ink {
+	    assert!(x.abs() <= 3.0 && y.abs() <= 3.0, "seed {} ({}, {})", seed, x, y);
+	}
+    }
+}
+
+#[test]
+fn copies_of_samples() {
+    let samples = Samples {
+	labels: vec![3, 7].into_boxed_slice(),
+	images: [dot(4, 4), dot(1, 1)].concat().into_boxed_slice(),
+	rows: SIZE,
+	cols: SIZE,
+	kind: DatasetKind::Mnist,
+	names: ClassNames::digits()
+    };
+    let mut done = Vec::new();
+    let (labels, images) = augment_samples(&samples, &Augmentation::default(), 2, true, 0, |no| done.push(no)).unwrap();
+    assert_eq!(done, vec![1, 2]);
+    assert_eq!(labels.data, IdxData::UInt8(vec![3, 3, 3, 7, 7, 7].into_boxed_slice()));
+    assert_eq!(images.shape, vec![6, SIZE, SIZE]);
+    match images.data {
+	IdxData::UInt8(values) => assert!(values.chunks(SIZE*SIZE).zip([0, 0, 0, 1, 1, 1].iter())
+					  .all(|(image, &no)| image == &samples.images[no*SIZE*SIZE..(no+1)*SIZE*SIZE])),
+	_ => panic!("not u8")
+    }
+}
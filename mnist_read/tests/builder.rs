@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::Path;
+
+mod common;
+
+use common::temp_path;
+use mnist_read::{IdxData, IdxError};
+use mnist_read::builder::{BuildOptions, Invert, build_dataset, class_folders, preprocess};
+use mnist_read::export::write_png;
+
+// width*height of paper with a block of ink at (x, y) of block_width*block_height
+fn page(width:usize, height:usize, paper:u8, ink:u8, (x, y, block_width, block_height):(usize, usize, usize, usize)) -> Vec<u8> {
+    (0..width*height).map(|pos| {
+	let (px, py) = (pos % width, pos / width);
+	if px >= x && px < x+block_width && py >= y && py < y+block_height { ink } else { paper }
+    }).collect()
+}
+
+fn write_page<P:AsRef<Path>>(path:P, width:usize, height:usize, block:(usize, usize, usize, usize)) {
+    fs::create_dir_all(path.as_ref().parent().unwrap()).unwrap();
+    write_png(path, &page(width, height, 255, 0, block), width, height).unwrap();
+}
+
+// [left, right) and [top, bottom) of non zero pixels of a size*size image
+fn ink_box(image:&[u8], size:usize) -> (usize, usize, usize, usize) {
+    let inked:Vec<usize> = (0..size*size).filter(|&pos| image[pos] > 0).collect();
+    let xs = inked.iter().map(|pos| pos % size);
+    let ys = inked.iter().map(|pos| pos / size);
+    (xs.clone().min().unwrap(), xs.max().unwrap()+1, ys.clone().min().unwrap(), ys.max().unwrap()+1)
+}
+
+#[test]
+fn labels_from_folder_names() {
+    let dir = temp_path("builder_numbers");
+    write_page(dir.join("7").join("b.png"), 8, 8, (2, 2, 3, 3));
+    write_page(dir.join("7").join("a.png"), 8, 8, (2, 2, 3, 3));
+    write_page(dir.join("3").join("a.png"), 8, 8, (2, 2, 3, 3));
+    fs::write(dir.join("3").join("notes.txt"), "not an image").unwrap();
+    let folders = class_folders(&dir);
+    let built = folders.as_ref().ok().map(|folders| build_dataset(folders, &BuildOptions::default()));
+    fs::create_dir_all(dir.join("07")).unwrap();
+    let same_label = class_folders(&dir);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let folders = folders.unwrap();
+    let labels:Vec<(u8, &str, usize)> = folders.iter().map(|f| (f.label, f.name.as_str(), f.files.len())).collect();
+    assert_eq!(labels, vec![(3, "3", 1), (7, "7", 2)]);
+    assert!(folders[1].files[0].ends_with("a.png"));
+    let (labels, images) = built.unwrap().unwrap();
+    assert_eq!(labels.data, IdxData::UInt8(vec![3, 7, 7].into_boxed_slice()));
+    assert_eq!(images.shape, vec![3, 28, 28]);
+    assert!(matches!(same_label, Err(IdxError::Invalid(_))));
+}
+
+#[test]
+fn labels_in_order_of_names() {
+    let dir = temp_path("builder_names");
+    write_page(dir.join("dog").join("a.png"), 8, 8, (2, 2, 3, 3));
+    write_page(dir.join("cat").join("a.png"), 8, 8, (2, 2, 3, 3));
+    let folders = class_folders(&dir);
+    fs::remove_dir_all(&dir).unwrap();
+    let labels:Vec<(u8, String)> = folders.unwrap().into_iter().map(|f| (f.label, f.name)).collect();
+    assert_eq!(labels, vec![(0, "cat".to_string()), (1, "dog".to_string())]);
+}
+
+#[test]
+fn fitted_and_inverted() {
+    // a black block of 4x2 on white paper is made white, scaled to 20x10 and centred in 28x28
+    let pixels = page(10, 10, 255, 0, (3, 5, 4, 2));
+    let image = preprocess(&pixels, 10, 10, &BuildOptions::default());
+    assert_eq!(image.len(), 28*28);
+    assert_eq!(ink_box(&image, 28), (4, 24, 9, 19));
+    assert!(image.iter().all(|&p| p == 0 || p == 255));
+
+    // the same with white ink on black paper, which is not inverted
+    let pixels = page(10, 10, 0, 255, (3, 5, 4, 2));
+    let options = BuildOptions { invert: Invert::Never, ..BuildOptions::default() };
+    assert_eq!(&preprocess(&pixels, 10, 10, &options)[..], &image[..]);
+    // inverting it makes black ink on white paper, and the paper is removed as the background
+    let options = BuildOptions { invert: Invert::Always, ..BuildOptions::default() };
+    assert!(preprocess(&pixels, 10, 10, &options).iter().all(|&p| p == 0));
+}
+
+#[test]
+fn images_of_different_sizes() {
+    let dir = temp_path("builder_sizes");
+    write_page(dir.join("0").join("small.png"), 6, 6, (1, 1, 2, 4));
+    write_page(dir.join("1").join("wide.png"), 40, 12, (5, 2, 30, 8));
+    let result = class_folders(&dir).and_then(|folders| build_dataset(&folders, &BuildOptions::for_size(16)));
+    fs::remove_dir_all(&dir).unwrap();
+
+    let (labels, images) = result.unwrap();
+    assert_eq!(labels.shape, vec![2]);
+    assert_eq!(images.shape, vec![2, 16, 16]);
+    let images = match images.data {
+	IdxData::UInt8(values) => values,
+	_ => panic!("not u8")
+    };
+    // the longer side of each block is fitted to 11 pixels
+    let (left, right, top, bottom) = ink_box(&images[..256], 16);
+    assert_eq!((right-left, bottom-top), (6, 11));
+    let (left, right, top, bottom) = ink_box(&images[256..], 16);
+    assert_eq!((right-left, bottom-top), (11, 3));
+}
@@ -0,0 +1,45 @@
+use std::fs;
+
+mod common;
+
+use common::temp_path;
+use mnist_read::IdxError;
+use mnist_read::classes::{ClassNames, DatasetKind};
+
+#[test]
+fn mapping_next_to_images() {
+    let dir = temp_path("classes_found");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("emnist-letters-mapping.txt"), "1 65 97\n2 66 98\n").unwrap();
+    let result = ClassNames::for_dataset(DatasetKind::Emnist, dir.join("emnist-letters-train-images-idx3-ubyte.gz"), None);
+    fs::remove_dir_all(&dir).unwrap();
+    let (names, warning) = result.unwrap();
+    assert_eq!((names.name(1), names.name(2), names.name(3)), (Some("Aa"), Some("Bb"), None));
+    assert!(warning.is_none());
+}
+
+#[test]
+fn missing_mapping_falls_back() {
+    let dir = temp_path("classes_missing");
+    let (names, warning) = ClassNames::for_dataset(DatasetKind::Emnist, dir.join("emnist-letters-train-images-idx3-ubyte.gz"), None).unwrap();
+    assert_eq!((names.name(10), names.name(36)), (Some("A"), Some("a")));
+    let warning = warning.unwrap();
+    assert!(warning.contains("emnist-letters-mapping.txt"), "{}", warning);
+
+    // the split is unknown from the file name
+    let (names, warning) = ClassNames::for_dataset(DatasetKind::Emnist, dir.join("train-images-idx3-ubyte"), None).unwrap();
+    assert_eq!(names.name(46), Some("t"));
+    assert!(warning.is_some());
+
+    // the file given explicitly is not replaced
+    let mapping_file = dir.join("mapping.txt");
+    let result = ClassNames::for_dataset(DatasetKind::Emnist, dir.join("train-images-idx3-ubyte"), Some(&mapping_file));
+    assert!(matches!(result, Err(IdxError::Invalid(_))));
+}
+
+#[test]
+fn other_datasets() {
+    let (names, warning) = ClassNames::for_dataset(DatasetKind::Mnist, "train-images-idx3-ubyte", None).unwrap();
+    assert_eq!(names.name(7), Some("7"));
+    assert!(warning.is_none());
+}
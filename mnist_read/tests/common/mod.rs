@@ -0,0 +1,40 @@
+// fixtures shared by the integration tests
+// each test crate uses only some of them
+#![allow(dead_code)]
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+use mnist_read::{IdxArray, IdxData, write_idx_to};
+
+// one array of shape [2, 3] for each value type, with values at the limits of the type
+pub fn arrays() -> Vec<IdxArray> {
+    let data = vec![
+	IdxData::UInt8(vec![0, 1, 2, 127, 128, 255].into_boxed_slice()),
+	IdxData::Int8(vec![0, 1, -1, 127, -128, 5].into_boxed_slice()),
+	IdxData::Int16(vec![0, 1, -1, 300, i16::MAX, i16::MIN].into_boxed_slice()),
+	IdxData::Int32(vec![0, 1, -1, 70000, i32::MAX, i32::MIN].into_boxed_slice()),
+	IdxData::Float32(vec![0.0, 1.5, -2.25, f32::MAX, f32::MIN_POSITIVE, f32::INFINITY].into_boxed_slice()),
+	IdxData::Float64(vec![0.0, 1.5, -2.25, f64::MAX, f64::MIN_POSITIVE, f64::NEG_INFINITY].into_boxed_slice())
+    ];
+    data.into_iter().map(|data| IdxArray::new(vec![2, 3], data).unwrap()).collect()
+}
+
+// 2x3 images whose pixels are no*10 + pos, labelled no % 3
+pub fn synthetic(count:usize) -> (IdxArray, IdxArray) {
+    let labels = (0..count).map(|no| (no % 3) as u8).collect();
+    let images = (0..count*6).map(|i| ((i / 6)*10 + i % 6) as u8).collect();
+    (IdxArray::new(vec![count], IdxData::UInt8(labels)).unwrap(),
+     IdxArray::new(vec![count, 2, 3], IdxData::UInt8(images)).unwrap())
+}
+
+pub fn to_bytes(array:&IdxArray) -> Vec<u8> {
+    let mut bytes:Vec<u8> = Vec::new();
+    write_idx_to(&mut bytes, array).unwrap();
+    bytes
+}
+
+pub fn temp_path(name:&str) -> PathBuf {
+    env::temp_dir().join(format!("mnist_read_test_{}_{}", process::id(), name))
+}
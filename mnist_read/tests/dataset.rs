@@ -0,0 +1,157 @@
+use std::fs;
+use std::io::Cursor;
+
+mod common;
+
+use common::{synthetic, temp_path, to_bytes};
+use mnist_read::{IdxArray, IdxData, IdxDataset, IdxError, IdxReader, read_idx_from, write_idx};
+
+fn dataset(count:usize) -> IdxDataset<Cursor<Vec<u8>>> {
+    let (labels, images) = synthetic(count);
+    IdxDataset::new(Cursor::new(to_bytes(&labels)), Cursor::new(to_bytes(&images))).unwrap()
+}
+
+#[test]
+fn random_access() {
+    let mut dataset = dataset(12);
+    assert_eq!(dataset.len(), 12);
+    assert_eq!((dataset.rows(), dataset.cols()), (2, 3));
+    for &no in [7, 0, 11, 3, 7].iter() {
+	let (label, image) = dataset.get(no).unwrap();
+	assert_eq!(label, (no % 3) as u8);
+	let expected:Vec<u8> = (0..6).map(|pos| (no*10 + pos) as u8).collect();
+	assert_eq!(&image[..], &expected[..]);
+    }
+}
+
+#[test]
+fn index_out_of_range() {
+    let mut dataset = dataset(4);
+    match dataset.get(4) {
+	Err(IdxError::OutOfRange { index: 4, len: 4 }) => (),
+	other => panic!("unexpected {:?}", other.map(|(label, _)| label))
+    }
+    assert!(matches!(dataset.image(usize::MAX), Err(IdxError::OutOfRange { .. })));
+    // the dataset is still usable after the error
+    assert_eq!(dataset.label(3).unwrap(), 0);
+}
+
+#[test]
+fn read_items_by_range() {
+    let (_, images) = synthetic(5);
+    let mut reader = IdxReader::new(Cursor::new(to_bytes(&images))).unwrap();
+    assert_eq!(reader.item_shape(), &[2, 3]);
+    match reader.read_items(1..3).unwrap() {
+	IdxData::UInt8(values) => assert_eq!(&values[..], &[10, 11, 12, 13, 14, 15, 20, 21, 22, 23, 24, 25]),
+	_ => panic!("not u8")
+    }
+    assert_eq!(reader.read_items(2..2).unwrap().len(), 0);
+    assert!(matches!(reader.read_items(3..6), Err(IdxError::OutOfRange { index: 6, len: 5 })));
+    let (start, end) = (3, 1);
+    assert!(matches!(reader.read_items(start..end), Err(IdxError::Invalid(_))));
+}
+
+#[test]
+fn typed_items() {
+    let array = IdxArray::new(vec![3, 2], IdxData::Int16(vec![1, -2, 300, -400, 5, 6].into_boxed_slice())).unwrap();
+    let mut reader = IdxReader::new(Cursor::new(to_bytes(&array))).unwrap();
+    match reader.read_item(1).unwrap() {
+	IdxData::Int16(values) => assert_eq!(&values[..], &[300, -400]),
+	_ => panic!("not i16")
+    }
+}
+
+#[test]
+fn count_mismatch() {
+    let (labels, _) = synthetic(5);
+    let (_, images) = synthetic(4);
+    match IdxDataset::new(Cursor::new(to_bytes(&labels)), Cursor::new(to_bytes(&images))) {
+	Err(IdxError::Invalid(message)) => assert_eq!(message, "5 labels do not match 4 images"),
+	_ => panic!("mismatch is accepted")
+    }
+}
+
+#[test]
+fn wrong_dimensions() {
+    let (labels, images) = synthetic(3);
+    // labels and images are swapped
+    let result = IdxDataset::new(Cursor::new(to_bytes(&images)), Cursor::new(to_bytes(&labels)));
+    assert!(matches!(result, Err(IdxError::Invalid(_))));
+}
+
+#[test]
+fn truncated_header() {
+    let (_, images) = synthetic(3);
+    let bytes = to_bytes(&images);
+    // magic and 2 of 3 dimensions
+    match IdxReader::new(Cursor::new(bytes[..12].to_vec())) {
+	Err(IdxError::Truncated { expected: 16, actual: 12 }) => (),
+	_ => panic!("short header is accepted")
+    }
+}
+
+#[test]
+fn truncated_values() {
+    let (_, images) = synthetic(3);
+    let bytes = to_bytes(&images);
+    match IdxReader::new(Cursor::new(bytes[..bytes.len()-1].to_vec())) {
+	Err(IdxError::Truncated { expected: 34, actual: 33 }) => (),
+	_ => panic!("short values are accepted")
+    }
+}
+
+fn raw_header(code:u8, shape:&[u32]) -> Vec<u8> {
+    let mut bytes = vec![0, 0, code, shape.len() as u8];
+    shape.iter().for_each(|dim| bytes.extend_from_slice(&dim.to_be_bytes()));
+    bytes
+}
+
+#[test]
+fn huge_shape() {
+    // the number of bytes overflows usize
+    let bytes = raw_header(0x0e, &[u32::MAX, u32::MAX, u32::MAX]);
+    assert!(matches!(IdxReader::new(Cursor::new(bytes.clone())), Err(IdxError::Invalid(_))));
+    assert!(matches!(read_idx_from(Cursor::new(bytes)), Err(IdxError::Invalid(_))));
+    // 4 GiB are declared, but only 3 bytes follow
+    let mut bytes = raw_header(0x08, &[0x10000000, 16]);
+    bytes.extend_from_slice(&[1, 2, 3]);
+    match read_idx_from(Cursor::new(bytes)) {
+	Err(IdxError::Truncated { expected, actual: 15 }) => assert_eq!(expected, 12 + (1 << 32)),
+	_ => panic!("short values are accepted")
+    }
+}
+
+#[test]
+fn trailing_data() {
+    let (_, images) = synthetic(3);
+    let mut bytes = to_bytes(&images);
+    bytes.extend_from_slice(&[0, 0]);
+    match IdxReader::new(Cursor::new(bytes)) {
+	Err(IdxError::TrailingData { expected: 34, actual: 36 }) => (),
+	_ => panic!("trailing data is accepted")
+    }
+}
+
+#[test]
+fn bad_magic() {
+    let (labels, _) = synthetic(3);
+    let mut bytes = to_bytes(&labels);
+    bytes[0] = 1;
+    assert!(matches!(IdxReader::new(Cursor::new(bytes)), Err(IdxError::BadMagic(0x01000801))));
+}
+
+#[test]
+fn open_files() {
+    let (labels, images) = synthetic(6);
+    let labels_file = temp_path("labels-idx1-ubyte");
+    let images_file = temp_path("images-idx3-ubyte.gz");
+    write_idx(&labels_file, &labels).unwrap();
+    write_idx(&images_file, &images).unwrap();
+
+    let result = IdxDataset::open(&labels_file, &images_file).and_then(|mut dataset| dataset.get(5));
+    fs::remove_file(&labels_file).unwrap();
+    fs::remove_file(&images_file).unwrap();
+    let (label, image) = result.unwrap();
+    assert_eq!(label, 2);
+    assert_eq!(&image[..], &[50, 51, 52, 53, 54, 55]);
+}
@@ -0,0 +1,72 @@
+use std::fs;
+use std::io::Cursor;
+
+mod common;
+
+use common::{arrays, temp_path, to_bytes};
+use mnist_read::{IdxArray, IdxData, IdxError, IdxType, read_idx, read_idx_from, write_idx};
+
+#[test]
+fn round_trip() {
+    let types = [IdxType::UInt8, IdxType::Int8, IdxType::Int16, IdxType::Int32, IdxType::Float32, IdxType::Float64];
+    for (array, value_type) in arrays().iter().zip(types.iter()) {
+	let bytes = to_bytes(array);
+	// magic, 2 dimensions and big endian values
+	assert_eq!(&bytes[..4], &[0, 0, value_type.code(), 2]);
+	assert_eq!(&bytes[4..12], &[0, 0, 0, 2, 0, 0, 0, 3]);
+	assert_eq!(bytes.len(), 12 + 6*value_type.size());
+	assert_eq!(&read_idx_from(Cursor::new(bytes)).unwrap(), array);
+    }
+}
+
+#[test]
+fn big_endian() {
+    let array = IdxArray::new(vec![2], IdxData::Int16(vec![0x0102, -2].into_boxed_slice())).unwrap();
+    assert_eq!(&to_bytes(&array)[8..], &[0x01, 0x02, 0xff, 0xfe]);
+}
+
+#[test]
+fn gzip_round_trip() {
+    for (i, array) in arrays().iter().enumerate() {
+	let path = temp_path(&format!("{}-idx2.gz", i));
+	write_idx(&path, array).unwrap();
+	let compressed = fs::read(&path).unwrap();
+	let result = read_idx(&path);
+	fs::remove_file(&path).unwrap();
+	assert_eq!(&compressed[..2], &[0x1f, 0x8b]);
+	assert_eq!(&result.unwrap(), array);
+    }
+}
+
+#[test]
+fn trailing_data() {
+    let array = &arrays()[3];
+    let mut bytes = to_bytes(array);
+    bytes.push(0);
+    match read_idx_from(Cursor::new(bytes)) {
+	Err(IdxError::TrailingData { expected: 36, actual: 37 }) => (),
+	_ => panic!("trailing data is accepted")
+    }
+}
+
+#[test]
+fn truncated() {
+    let bytes = to_bytes(&arrays()[5]);
+    match read_idx_from(Cursor::new(bytes[..bytes.len()-3].to_vec())) {
+	Err(IdxError::Truncated { expected: 60, actual: 57 }) => (),
+	_ => panic!("short values are accepted")
+    }
+}
+
+#[test]
+fn unsupported_type() {
+    let mut bytes = to_bytes(&arrays()[0]);
+    bytes[2] = 0x0a;
+    assert!(matches!(read_idx_from(Cursor::new(bytes)), Err(IdxError::UnsupportedType(0x0a))));
+}
+
+#[test]
+fn shape_mismatch() {
+    let result = IdxArray::new(vec![2, 2], IdxData::UInt8(vec![0; 3].into_boxed_slice()));
+    assert!(matches!(result, Err(IdxError::Invalid(_))));
+}
@@ -0,0 +1,71 @@
+use mnist_read::model::{ModelError, Network};
+use numpy_read::{NpyArray, NpyData};
+
+fn array(shape:&[usize], values:&[f32]) -> NpyArray {
+    NpyArray {
+	shape: shape.to_vec(),
+	data: NpyData::Float32(values.to_vec().into_boxed_slice())
+    }
+}
+
+// 4 inputs, 2 and 2 hidden units and 3 classes. the first hidden unit fires on pixel 0 and
+// the second on pixel 1, the second layer passes them on, and they vote for class 2 and 0
+fn network() -> Network {
+    let arrays = vec![
+	("w1".to_string(), array(&[4, 2], &[10.0, -10.0,  -10.0, 10.0,  0.0, 0.0,  0.0, 0.0])),
+	("b1".to_string(), array(&[2], &[0.0, 0.0])),
+	("w2".to_string(), array(&[2, 2], &[10.0, 0.0,  0.0, 10.0])),
+	("b2".to_string(), array(&[2], &[-5.0, -5.0])),
+	("w3".to_string(), array(&[2, 3], &[0.0, 0.0, 4.0,  4.0, 0.0, 0.0])),
+	("b3".to_string(), array(&[3], &[0.0, 0.0, 0.0]))
+    ];
+    Network::from_arrays(&arrays).unwrap()
+}
+
+fn argmax(values:&[f32]) -> usize {
+    (0..values.len()).fold(0, |best, i| if values[i] > values[best] { i } else { best })
+}
+
+#[test]
+fn forward_pass() {
+    let network = network();
+    assert_eq!((network.input_size(), network.output_size()), (4, 3));
+
+    let probabilities = network.predict(&[255, 0, 0, 0]).unwrap();
+    assert_eq!(argmax(&probabilities), 2);
+    assert!((probabilities.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+    // sigmoid(10) and sigmoid(-10) at the first layer, then sigmoid(5) and sigmoid(-5)
+    let sigmoid = |a:f64| 1.0/(1.0 + (-a).exp());
+    let hidden = [sigmoid(10.0*sigmoid(10.0) - 5.0), sigmoid(10.0*sigmoid(-10.0) - 5.0)];
+    let a = [4.0*hidden[1], 0.0, 4.0*hidden[0]];
+    let sum:f64 = a.iter().map(|a| a.exp()).sum();
+    for (p, a) in probabilities.iter().zip(a.iter()) {
+	assert!((*p as f64 - a.exp()/sum).abs() < 1e-5, "{:?}", probabilities);
+    }
+
+    assert_eq!(argmax(&network.predict(&[0, 255, 0, 0]).unwrap()), 0);
+    // pixels 2 and 3 are not connected
+    assert_eq!(network.predict(&[255, 0, 0, 0]).unwrap(), network.predict(&[255, 0, 255, 128]).unwrap());
+}
+
+#[test]
+fn input_size_mismatch() {
+    assert!(matches!(network().predict(&[0; 5]), Err(ModelError::Invalid(_))));
+}
+
+#[test]
+fn layers_not_connected() {
+    let arrays = vec![
+	("w1".to_string(), array(&[4, 2], &[0.0; 8])),
+	("b1".to_string(), array(&[2], &[0.0; 2])),
+	("w2".to_string(), array(&[3, 2], &[0.0; 6])),
+	("b2".to_string(), array(&[2], &[0.0; 2])),
+	("w3".to_string(), array(&[2, 3], &[0.0; 6])),
+	("b3".to_string(), array(&[3], &[0.0; 3]))
+    ];
+    match Network::from_arrays(&arrays) {
+	Err(ModelError::Invalid(message)) => assert!(message.contains("w2"), "{}", message),
+	_ => panic!("w2 of 3 inputs is accepted")
+    }
+    assert!(matches!(Network::from_arrays(&arrays[..5]), Err(ModelError::Invalid(_))));
+}
@@ -0,0 +1,66 @@
+use mnist_read::{IdxArray, IdxData, Samples};
+use mnist_read::classes::{ClassNames, DatasetKind};
+use mnist_read::stats::{compute, find_duplicates};
+
+// 2x2 images of the pixels one after another
+fn images(pixels:&[u8]) -> IdxArray {
+    IdxArray::new(vec![pixels.len()/4, 2, 2], IdxData::UInt8(pixels.to_vec().into_boxed_slice())).unwrap()
+}
+
+fn pixels(array:&IdxArray) -> &[u8] {
+    match array.data {
+	IdxData::UInt8(ref values) => values,
+	_ => panic!("not u8")
+    }
+}
+
+#[test]
+fn duplicates_across_train_and_test() {
+    let train = images(&[1, 2, 3, 4,  5, 6, 7, 8,  1, 2, 3, 4,  0, 0, 0, 0]);
+    let test = images(&[9, 9, 9, 9,  5, 6, 7, 8,  1, 2, 3, 4,  0, 0, 0, 1]);
+    // test images are numbered after the 4 train images
+    let all = [pixels(&train), pixels(&test)].concat();
+    assert_eq!(find_duplicates(&all, 4), vec![vec![0, 2, 6], vec![1, 5]]);
+    assert_eq!(find_duplicates(pixels(&test), 4), Vec::<Vec<usize>>::new());
+}
+
+#[test]
+fn no_images() {
+    assert!(find_duplicates(&[], 4).is_empty());
+    assert!(find_duplicates(&[1, 1], 0).is_empty());
+
+    let samples = Samples {
+	labels: Vec::new().into_boxed_slice(),
+	images: Vec::new().into_boxed_slice(),
+	rows: 2,
+	cols: 2,
+	kind: DatasetKind::Mnist,
+	names: ClassNames::digits()
+    };
+    let stats = compute(&samples);
+    assert_eq!(stats.count, 0);
+    assert!(stats.classes.is_empty());
+    assert_eq!((&stats.mean, &stats.variance), (&vec![0.0; 4], &vec![0.0; 4]));
+    assert_eq!(stats.histogram.iter().sum::<usize>(), 0);
+    assert_eq!(stats.duplicate_count(), 0);
+}
+
+#[test]
+fn class_counts_and_moments() {
+    let samples = Samples {
+	labels: vec![1, 0, 1].into_boxed_slice(),
+	images: vec![0, 10, 20, 30,  0, 10, 20, 30,  6, 10, 20, 0].into_boxed_slice(),
+	rows: 2,
+	cols: 2,
+	kind: DatasetKind::Mnist,
+	names: ClassNames::digits()
+    };
+    let stats = compute(&samples);
+    let counts:Vec<(u8, Option<&str>, usize)> = stats.classes.iter().map(|c| (c.label, c.name.as_deref(), c.count)).collect();
+    assert_eq!(counts, vec![(0, Some("0"), 1), (1, Some("1"), 2)]);
+    assert_eq!(stats.mean, vec![2.0, 10.0, 20.0, 20.0]);
+    assert_eq!(stats.variance, vec![8.0, 0.0, 0.0, 200.0]);
+    assert_eq!((stats.histogram[0], stats.histogram[10], stats.histogram[30]), (3, 3, 2));
+    assert_eq!(stats.duplicates, vec![vec![0, 1]]);
+    assert_eq!(stats.duplicate_count(), 1);
+}
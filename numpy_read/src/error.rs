@@ -0,0 +1,105 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+use crate::header::HeaderError;
+
+#[derive(Debug)]
+pub enum NpyError {
+    // magic string is not "\x93NUMPY"
+    BadMagic,
+    // major and minor version in the preamble
+    UnsupportedVersion(u8, u8),
+    // offset is the byte offset in the header string
+    Header { offset: usize, message: String },
+    // header is a valid literal, but the descr is broken
+    InvalidDescr(String),
+    UnsupportedDtype(String),
+    // bytes required and bytes actually read
+    Truncated { expected: usize, actual: usize },
+    Io(io::Error),
+    Archive(zip::result::ZipError),
+    Hdf5(hdf5::Error),
+    // valid npy, but this tool does not handle it
+    Unsupported(String)
+}
+
+impl NpyError {
+    /// Exit status of the command for each kind of error.
+    /// 1 is left for failures which are not NpyError and 2 for command line errors.
+    pub fn exit_code(&self) -> i32 {
+	match self {
+	    NpyError::BadMagic => 3,
+	    NpyError::UnsupportedVersion(_, _) => 4,
+	    NpyError::Header { .. } => 5,
+	    NpyError::InvalidDescr(_) => 6,
+	    NpyError::UnsupportedDtype(_) => 7,
+	    NpyError::Truncated { .. } => 8,
+	    NpyError::Io(_) => 9,
+	    NpyError::Archive(_) => 10,
+	    NpyError::Hdf5(_) => 11,
+	    NpyError::Unsupported(_) => 12
+	}
+    }
+}
+
+impl fmt::Display for NpyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    NpyError::BadMagic => write!(f, "magic is invalid"),
+	    NpyError::UnsupportedVersion(major, minor) => write!(f, "unknown format version {}.{}", major, minor),
+	    NpyError::Header { offset, message } => write!(f, "invalid header. {} at {}", message, offset),
+	    NpyError::InvalidDescr(message) => write!(f, "invalid descr. {}", message),
+	    NpyError::UnsupportedDtype(descr) => write!(f, "not support value format '{}'", descr),
+	    NpyError::Truncated { expected, actual } => {
+		write!(f, "file size is too short. {} bytes are required, but {} bytes are read", expected, actual)
+	    },
+	    NpyError::Io(err) => write!(f, "{}", err),
+	    NpyError::Archive(err) => write!(f, "{}", err),
+	    NpyError::Hdf5(hdf5::Error::HDF5(es)) => {
+		// the error stack of hdf5 library is expanded to the innermost description
+		match es.expand() {
+		    Ok(expanded_es) => write!(f, "{}", expanded_es.description()),
+		    Err(_) => write!(f, "hdf5 internal error")
+		}
+	    },
+	    NpyError::Hdf5(err) => write!(f, "{}", err),
+	    NpyError::Unsupported(message) => write!(f, "{}", message)
+	}
+    }
+}
+
+impl error::Error for NpyError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+	match self {
+	    NpyError::Io(err) => Some(err),
+	    NpyError::Archive(err) => Some(err),
+	    NpyError::Hdf5(err) => Some(err),
+	    _ => None
+	}
+    }
+}
+
+impl From<io::Error> for NpyError {
+    fn from(err:io::Error) -> NpyError {
+	NpyError::Io(err)
+    }
+}
+
+impl From<zip::result::ZipError> for NpyError {
+    fn from(err:zip::result::ZipError) -> NpyError {
+	NpyError::Archive(err)
+    }
+}
+
+impl From<hdf5::Error> for NpyError {
+    fn from(err:hdf5::Error) -> NpyError {
+	NpyError::Hdf5(err)
+    }
+}
+
+impl From<HeaderError> for NpyError {
+    fn from(err:HeaderError) -> NpyError {
+	NpyError::Header { offset: err.position, message: err.message }
+    }
+}
@@ -0,0 +1,410 @@
+use std::cmp;
+use std::fs;
+use std::path::Path;
+use std::io::Read;
+use half::f16;
+use num_complex::Complex;
+
+use hdf5::types::{FloatSize, IntSize, TypeDescriptor, VarLenUnicode};
+use hdf5::{Hyperslab, SliceOrIndex};
+use ndarray::{ArrayView, IxDyn};
+
+use crate::{Descr, ElementLayout, NpyArray, NpyData, NpyError, NpyFormat, NpyMmap, NpyVersion, ReadOptions, ValueType};
+use crate::{decode_data, open_npy_stream, read_full, read_value_in_order, stream_array_name, writer};
+
+/// Layout of datasets created by this module.
+#[derive(Debug,Clone,Default)]
+pub struct WriteOptions {
+    // chunk shape. missing dimensions are chunked by the whole extent
+    pub chunk: Option<Vec<usize>>,
+    // deflate level from 0 to 9
+    pub gzip: Option<u8>,
+    pub shuffle: bool,
+    // arrays in Fortran order are written as stored, that is their transpose,
+    // with the attribute storage_order = "F"
+    pub keep_fortran_order: bool
+}
+
+/// Attributes which record where the dataset comes from.
+#[derive(Debug,Clone)]
+pub struct SourceAttributes {
+    pub source: String,
+    pub descr: String,
+    pub fortran_order: bool
+}
+
+impl SourceAttributes {
+    pub fn new(source:&str, format:&NpyFormat) -> SourceAttributes {
+	SourceAttributes {
+	    source: source.to_string(),
+	    descr: format.descr.to_string(),
+	    fortran_order: format.fortran_order
+	}
+    }
+}
+
+/// Attributes written to each dataset, or the group of structured array.
+#[derive(Debug,Clone,Default)]
+pub struct DatasetAttributes {
+    pub source: Option<SourceAttributes>,
+    // the dataset is the transpose of the array, since values are kept in Fortran order
+    pub transposed: bool
+}
+
+impl DatasetAttributes {
+    pub fn new(source:Option<&str>, format:&NpyFormat, options:&WriteOptions) -> DatasetAttributes {
+	DatasetAttributes {
+	    source: source.map(|source| SourceAttributes::new(source, format)),
+	    transposed: options.keep_fortran_order && format.fortran_order && format.shape.len() > 1
+	}
+    }
+}
+
+fn create_dataset<T:hdf5::H5Type>(output:&hdf5::Group, name:&str, shape:&[usize], options:&WriteOptions) -> Result<hdf5::Dataset,hdf5::Error> {
+    let builder = output.new_dataset::<T>();
+    if shape.is_empty() {
+	// 0-D array is stored as a scalar dataset, which can not be chunked
+	return builder.shape(()).create(name);
+    }
+
+    let mut builder = builder.shape(shape);
+    if let Some(ref chunk) = options.chunk {
+	let chunk_shape:Vec<usize> = shape.iter().enumerate().map(|(i, &dim)| {
+	    cmp::max(cmp::min(*chunk.get(i).unwrap_or(&dim), dim), 1)
+	}).collect();
+	builder = builder.chunk(chunk_shape);
+    }
+    if options.shuffle {
+	builder = builder.shuffle();
+    }
+    if let Some(level) = options.gzip {
+	builder = builder.deflate(level);
+    }
+    builder.create(name)
+}
+
+fn write_attributes(location:&hdf5::Location, attributes:&DatasetAttributes) -> Result<(),hdf5::Error> {
+    let to_unicode = |s:&str| -> Result<VarLenUnicode,hdf5::Error> {
+	s.parse::<VarLenUnicode>().map_err(|err| hdf5::Error::Internal(err.to_string()))
+    };
+    if let Some(ref source) = attributes.source {
+	location.new_attr::<VarLenUnicode>().shape(()).create("source")?.write_scalar(&to_unicode(&source.source)?)?;
+	location.new_attr::<VarLenUnicode>().shape(()).create("dtype")?.write_scalar(&to_unicode(&source.descr)?)?;
+	location.new_attr::<bool>().shape(()).create("fortran_order")?.write_scalar(&source.fortran_order)?;
+    }
+    if attributes.transposed {
+	location.new_attr::<VarLenUnicode>().shape(()).create("storage_order")?.write_scalar(&to_unicode("F")?)?;
+    }
+    Ok(())
+}
+
+/// Opens the group of the path, creating missing groups on the way.
+pub fn open_group(output:&hdf5::Group, path:&str) -> Result<hdf5::Group,hdf5::Error> {
+    let mut group = output.group("/")?;
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+	group = if group.link_exists(name) {
+	    group.group(name)?
+	}
+	else {
+	    group.create_group(name)?
+	};
+    }
+    Ok(group)
+}
+
+pub fn write_dataset<T:hdf5::H5Type>(output:&hdf5::Group, name:&str, shape:&[usize], values:&[T],
+				     options:&WriteOptions) -> Result<hdf5::Dataset,hdf5::Error> {
+    let data_set = create_dataset::<T>(output, name, shape, options)?;
+    data_set.write_raw(values)?;
+    Ok(data_set)
+}
+
+/// Writes the array as a dataset. Structured array is written as a group which has one dataset per field,
+/// and the attributes are written only to the group.
+pub fn write_array(output:&hdf5::Group, name:&str, array:&NpyArray, options:&WriteOptions,
+		   attributes:&DatasetAttributes) -> Result<(),hdf5::Error> {
+    crate::dispatch_npy_data!(&array.data,
+			      values => {
+				  let data_set = write_dataset(output, name, &array.shape, values, options)?;
+				  write_attributes(&data_set, attributes)
+			      },
+			      fields => {
+				  let group = output.create_group(name)?;
+				  for (field_name, field) in fields.iter() {
+				      write_array(&group, field_name, field, options, &DatasetAttributes::default())?;
+				  }
+				  write_attributes(&group, attributes)
+			      })
+}
+
+fn write_slab<T:hdf5::H5Type>(output:&hdf5::Group, name:&str, shape:&[usize], options:&WriteOptions,
+			      data_set:&mut Option<hdf5::Dataset>,
+			      start:usize, slab_shape:&[usize], values:&[T]) -> Result<(),hdf5::Error> {
+    if data_set.is_none() {
+	*data_set = Some(create_dataset::<T>(output, name, shape, options)?);
+    }
+
+    let view = match ArrayView::from_shape(IxDyn(slab_shape), values) {
+	Ok(view) => view,
+	Err(err) => {
+	    return Err(hdf5::Error::Internal(err.to_string()));
+	}
+    };
+    let mut selection:Vec<SliceOrIndex> = vec![SliceOrIndex::from(start..start+slab_shape[0])];
+    selection.extend(slab_shape[1..].iter().map(|_| SliceOrIndex::from(..)));
+
+    data_set.as_ref().unwrap().write_slice(view, Hyperslab::from(selection))
+}
+
+/// Reads slab_rows rows along the first axis at a time and writes them into the dataset,
+/// so that the whole array is never held in memory.
+/// When with_attributes is true, the source file, dtype and fortran_order are recorded as attributes.
+/// Arrays in Fortran order of more than one dimension are not contiguous by rows,
+/// so they are streamed only when options.keep_fortran_order is true.
+/// npy_file "-" is the standard input, and arrays concatenated in the stream are written
+/// as name, name_1, name_2, ...
+/// Headers are checked by read_options, and read_options.as_stored is not used.
+pub fn stream_to_hdf5(npy_file:&str, output:&hdf5::Group, name:&str, slab_rows:usize,
+		      options:&WriteOptions, read_options:&ReadOptions, with_attributes:bool) -> Result<(),NpyError> {
+    let mut stream = open_npy_stream(npy_file, read_options)?;
+    let source = if with_attributes { Some(npy_file) } else { None };
+    let mut index = 0;
+    while let Some(format) = stream.next_format()? {
+	stream_values(stream.get_mut(), &format, output, &stream_array_name(name, index), slab_rows, options, source)?;
+	index += 1;
+    }
+    Ok(())
+}
+
+fn stream_values<R:Read>(f:&mut R, format:&NpyFormat, output:&hdf5::Group, name:&str, slab_rows:usize,
+			 options:&WriteOptions, source:Option<&str>) -> Result<(),NpyError> {
+    let attributes = DatasetAttributes::new(source, format, options);
+
+    if let Descr::Record(_, _) = format.descr {
+	return Err(NpyError::Unsupported("not support streaming of structured array".to_string()));
+    }
+
+    // values kept in Fortran order are the transpose in C order
+    let mut shape = format.shape.clone();
+    if attributes.transposed {
+	shape.reverse();
+    }
+
+    // reading rows of a Fortran order array would need the whole array in memory
+    if format.fortran_order && !attributes.transposed && shape.len() > 1 {
+	return Err(NpyError::Unsupported("not support streaming of Fortran order array, pass --keep_fortran_order to write it as stored".to_string()));
+    }
+
+    // scalar and empty arrays have no rows
+    if shape.is_empty() || shape[0] == 0 {
+	let array = read_value_in_order(f, format, attributes.transposed)?;
+	return Ok(write_array(output, name, &array, options, &attributes)?);
+    }
+
+    let rows = shape[0];
+    let row_items:usize = shape[1..].iter().product();
+    let item_size = format.descr.size();
+    let layout = ElementLayout {
+	record_size: item_size,
+	offset: 0,
+	sub_count: 1,
+	element_size: item_size
+    };
+
+    let mut data_set:Option<hdf5::Dataset> = None;
+    let mut bytes:Vec<u8> = Vec::new();
+    let mut start = 0;
+    while start < rows {
+	let end = cmp::min(start + cmp::max(slab_rows, 1), rows);
+	bytes.resize((end-start)*row_items*item_size, 0);
+	read_full(f, &mut bytes)?;
+
+	let mut slab_shape = shape.clone();
+	slab_shape[0] = end-start;
+	let slab = decode_data(&bytes, &slab_shape, &format.descr, &layout)?;
+	crate::dispatch_npy_data!(&slab,
+				  values => write_slab(output, name, &shape, options, &mut data_set, start, &slab_shape, values),
+				  _fields => Err(hdf5::Error::Internal("not support structured array".to_string())))?;
+	start = end;
+    }
+
+    if let Some(data_set) = data_set {
+	write_attributes(&data_set, &attributes)?;
+    }
+    Ok(())
+}
+
+/// Writes values straight from the mapping when they are in native byte order and C order.
+/// source is recorded as attributes when it is given.
+pub fn write_mmap(output:&hdf5::Group, name:&str, npy:&NpyMmap, options:&WriteOptions,
+		  source:Option<&str>) -> Result<(),NpyError> {
+    let attributes = DatasetAttributes::new(source, npy.format(), options);
+    let mut shape = npy.shape().to_vec();
+    if attributes.transposed {
+	shape.reverse();
+    }
+
+    macro_rules! write_slice {
+	($t:ty) => {
+	    match npy.as_slice::<$t>() {
+		Ok(values) => {
+		    Some(write_dataset(output, name, &shape, values, options).and_then(|data_set| {
+			write_attributes(&data_set, &attributes)
+		    }))
+		},
+		Err(_) => None
+	    }
+	}
+    }
+
+    let direct = match npy.format().descr {
+	Descr::Scalar(_, value_type) if !npy.format().fortran_order || shape.len() <= 1 || attributes.transposed => {
+	    match value_type {
+		ValueType::UInt8 => write_slice!(u8),
+		ValueType::Int8 => write_slice!(i8),
+		ValueType::UInt16 => write_slice!(u16),
+		ValueType::Int16 => write_slice!(i16),
+		ValueType::UInt32 => write_slice!(u32),
+		ValueType::Int32 => write_slice!(i32),
+		ValueType::UInt64 => write_slice!(u64),
+		ValueType::Int64 => write_slice!(i64),
+		ValueType::Float16 => write_slice!(f16),
+		ValueType::Float32 => write_slice!(f32),
+		ValueType::Float64 => write_slice!(f64),
+		ValueType::Complex64 => write_slice!(Complex<f32>),
+		ValueType::Complex128 => write_slice!(Complex<f64>),
+		ValueType::Bool => None
+	    }
+	},
+	_ => None
+    };
+
+    let result = match direct {
+	Some(result) => result,
+	None => {
+	    let array = if attributes.transposed { npy.to_array_as_stored()? } else { npy.to_array()? };
+	    write_array(output, name, &array, options, &attributes)
+	}
+    };
+    Ok(result?)
+}
+
+pub fn read_hdf5_dataset(data_set:&hdf5::Dataset) -> Result<NpyArray,hdf5::Error> {
+    let shape = data_set.shape();
+    let data = match data_set.dtype()?.to_descriptor()? {
+	TypeDescriptor::Boolean => NpyData::Bool(data_set.read_raw::<bool>()?.into_boxed_slice()),
+	TypeDescriptor::Unsigned(IntSize::U1) => NpyData::UInt8(data_set.read_raw::<u8>()?.into_boxed_slice()),
+	TypeDescriptor::Integer(IntSize::U1) => NpyData::Int8(data_set.read_raw::<i8>()?.into_boxed_slice()),
+	TypeDescriptor::Unsigned(IntSize::U2) => NpyData::UInt16(data_set.read_raw::<u16>()?.into_boxed_slice()),
+	TypeDescriptor::Integer(IntSize::U2) => NpyData::Int16(data_set.read_raw::<i16>()?.into_boxed_slice()),
+	TypeDescriptor::Unsigned(IntSize::U4) => NpyData::UInt32(data_set.read_raw::<u32>()?.into_boxed_slice()),
+	TypeDescriptor::Integer(IntSize::U4) => NpyData::Int32(data_set.read_raw::<i32>()?.into_boxed_slice()),
+	TypeDescriptor::Unsigned(IntSize::U8) => NpyData::UInt64(data_set.read_raw::<u64>()?.into_boxed_slice()),
+	TypeDescriptor::Integer(IntSize::U8) => NpyData::Int64(data_set.read_raw::<i64>()?.into_boxed_slice()),
+	TypeDescriptor::Float(FloatSize::U2) => NpyData::Float16(data_set.read_raw::<f16>()?.into_boxed_slice()),
+	TypeDescriptor::Float(FloatSize::U4) => NpyData::Float32(data_set.read_raw::<f32>()?.into_boxed_slice()),
+	TypeDescriptor::Float(FloatSize::U8) => NpyData::Float64(data_set.read_raw::<f64>()?.into_boxed_slice()),
+	TypeDescriptor::Compound(ref compound) if compound.fields.len() == 2 => {
+	    // complex numbers are stored as a compound of two floats
+	    match (&compound.fields[0].ty, &compound.fields[1].ty) {
+		(TypeDescriptor::Float(FloatSize::U4), TypeDescriptor::Float(FloatSize::U4)) => {
+		    NpyData::Complex64(data_set.read_raw::<Complex<f32>>()?.into_boxed_slice())
+		},
+		(TypeDescriptor::Float(FloatSize::U8), TypeDescriptor::Float(FloatSize::U8)) => {
+		    NpyData::Complex128(data_set.read_raw::<Complex<f64>>()?.into_boxed_slice())
+		},
+		_ => {
+		    return Err(hdf5::Error::Internal(format!("not support compound type of {}", data_set.name())));
+		}
+	    }
+	},
+	descriptor => {
+	    return Err(hdf5::Error::Internal(format!("not support {:?} of {}", descriptor, data_set.name())));
+	}
+    };
+
+    Ok(NpyArray {
+	shape: shape,
+	data: data
+    })
+}
+
+/// Collects every dataset under the group, named by its path relative to the root.
+pub fn read_hdf5_group(group:&hdf5::Group, arrays:&mut Vec<(String, NpyArray)>) -> Result<(),hdf5::Error> {
+    for data_set in group.datasets()? {
+	let array = read_hdf5_dataset(&data_set)?;
+	arrays.push((data_set.name().trim_start_matches('/').to_string(), array));
+    }
+    for sub_group in group.groups()? {
+	read_hdf5_group(&sub_group, arrays)?;
+    }
+    Ok(())
+}
+
+/// Writes every dataset of the hdf5 file into a npz file, or npy files under the output directory.
+pub fn export_hdf5(hdf5_file:&str, output:&str, version:Option<NpyVersion>) -> Result<(),NpyError> {
+
+    let mut arrays:Vec<(String, NpyArray)> = Vec::new();
+    let h = hdf5::File::open(hdf5_file)?;
+    read_hdf5_group(&h, &mut arrays)?;
+
+    let output_path = Path::new(output);
+    if output_path.extension().map_or(false, |ext| ext == "npz") {
+	return writer::write_npz(output, &arrays, version);
+    }
+
+    // one npy file per dataset. groups become sub directories.
+    for (name, array) in arrays.iter() {
+	let npy_path = output_path.join(format!("{}.npy", name));
+	if let Some(parent) = npy_path.parent() {
+	    fs::create_dir_all(parent)?;
+	}
+	writer::write_npy(&npy_path.to_string_lossy(), array, version)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::test_util::{i64_bytes, npy_bytes, temp_path};
+
+    // np.asfortranarray(np.arange(6).reshape(2, 3))
+    fn fortran_npy(name:&str) -> String {
+	let path = temp_path(name);
+	fs::write(&path, npy_bytes("'<i8'", true, "(2, 3)", &i64_bytes(&[0, 3, 1, 4, 2, 5]))).unwrap();
+	path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn stream_fortran_order() {
+	let npy_file = fortran_npy("fortran.npy");
+	let hdf5_file = temp_path("fortran.h5");
+	let read_options = ReadOptions::default();
+	let mut options = WriteOptions::default();
+	let result = {
+	    let output = hdf5::File::create(&hdf5_file).unwrap();
+	    let rejected = stream_to_hdf5(&npy_file, &output, "c", 1, &options, &read_options, false);
+	    options.keep_fortran_order = true;
+	    stream_to_hdf5(&npy_file, &output, "f", 1, &options, &read_options, false).unwrap();
+	    let mut arrays = Vec::new();
+	    read_hdf5_group(&output, &mut arrays).unwrap();
+	    (rejected, arrays)
+	};
+	fs::remove_file(&npy_file).unwrap();
+	fs::remove_file(&hdf5_file).unwrap();
+
+	match result.0 {
+	    Err(NpyError::Unsupported(message)) => assert!(message.contains("--keep_fortran_order"), "{}", message),
+	    other => panic!("unexpected {:?}", other)
+	}
+	// the transpose is written slab by slab
+	assert_eq!(result.1, vec![("f".to_string(), NpyArray {
+	    shape: vec![3, 2],
+	    data: NpyData::Int64(vec![0, 3, 1, 4, 2, 5].into_boxed_slice())
+	})]);
+    }
+}
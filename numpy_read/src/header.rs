@@ -0,0 +1,535 @@
+use std::fmt;
+
+// Parser of the npy header, which is a python dict literal written by repr().
+// Only the subset which NumPy writes is accepted:
+// strings, booleans, None, integers, tuples, lists and dicts.
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum PyValue {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    None,
+    Tuple(Vec<PyValue>),
+    List(Vec<PyValue>),
+    Dict(Vec<(PyValue, PyValue)>)
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct HeaderError {
+    // byte offset in the header string
+    pub position: usize,
+    pub message: String
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	write!(f, "{} at {}", self.message, self.position)
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct NpyHeader {
+    // either Str for simple types or List for structured types
+    pub descr: PyValue,
+    pub fortran_order: bool,
+    pub shape: Vec<usize>
+}
+
+#[derive(Debug,Clone,PartialEq)]
+enum Token {
+    LeftCurlyBracket, RightCurlyBracket,
+    LeftParenthesis, RightParenthesis,
+    LeftSquareBracket, RightSquareBracket,
+    Colon, Comma,
+    Str(String),
+    Int(i64),
+    Ident(String)
+}
+
+struct Tokenizer<'a> {
+    source: &'a str,
+    position: usize
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(source:&'a str) -> Tokenizer<'a> {
+	Tokenizer { source: source, position: 0 }
+    }
+
+    fn error<T>(&self, position:usize, message:&str) -> Result<T,HeaderError> {
+	Err(HeaderError { position: position, message: message.to_string() })
+    }
+
+    fn peek_char(&self) -> Option<char> {
+	self.source[self.position..].chars().next()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+	let c = self.peek_char()?;
+	self.position += c.len_utf8();
+	Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+	while let Some(c) = self.peek_char() {
+	    if !c.is_whitespace() {
+		break;
+	    }
+	    self.position += c.len_utf8();
+	}
+    }
+
+    fn read_string(&mut self, quote:char) -> Result<String,HeaderError> {
+	let start = self.position - 1;
+	let mut s = String::new();
+	loop {
+	    let c = match self.next_char() {
+		Some(c) => c,
+		None => {
+		    return self.error(start, "unterminated string");
+		}
+	    };
+	    if c == quote {
+		return Ok(s);
+	    }
+	    if c == '\n' {
+		return self.error(start, "unterminated string");
+	    }
+	    if c != '\\' {
+		s.push(c);
+		continue;
+	    }
+	    let escape_pos = self.position - 1;
+	    match self.next_char() {
+		Some('\\') => s.push('\\'),
+		Some('\'') => s.push('\''),
+		Some('"') => s.push('"'),
+		Some('n') => s.push('\n'),
+		Some('t') => s.push('\t'),
+		Some('r') => s.push('\r'),
+		Some('0') => s.push('\0'),
+		Some(e) if e == 'x' || e == 'u' || e == 'U' => {
+		    let digits = match e { 'x' => 2, 'u' => 4, _ => 8 };
+		    let end = self.position + digits;
+		    let code = self.source.get(self.position..end)
+			.and_then(|hex| u32::from_str_radix(hex, 16).ok())
+			.and_then(char::from_u32);
+		    match code {
+			Some(code) => {
+			    s.push(code);
+			    self.position = end;
+			},
+			None => {
+			    return self.error(escape_pos, "invalid escape sequence");
+			}
+		    }
+		},
+		_ => {
+		    return self.error(escape_pos, "invalid escape sequence");
+		}
+	    }
+	}
+    }
+
+    fn next_token(&mut self) -> Result<Option<(usize, Token)>,HeaderError> {
+	self.skip_whitespace();
+	let start = self.position;
+	let c = match self.next_char() {
+	    Some(c) => c,
+	    None => {
+		return Ok(None);
+	    }
+	};
+
+	let token = match c {
+	    '{' => Token::LeftCurlyBracket,
+	    '}' => Token::RightCurlyBracket,
+	    '(' => Token::LeftParenthesis,
+	    ')' => Token::RightParenthesis,
+	    '[' => Token::LeftSquareBracket,
+	    ']' => Token::RightSquareBracket,
+	    ':' => Token::Colon,
+	    ',' => Token::Comma,
+	    '\'' | '"' => Token::Str(self.read_string(c)?),
+	    '-' | '0'..='9' => {
+		while let Some(d) = self.peek_char() {
+		    if !d.is_ascii_digit() {
+			break;
+		    }
+		    self.position += 1;
+		}
+		let digits = &self.source[start..self.position];
+		// python2 repr writes long integers with a 'L' suffix
+		if self.peek_char() == Some('L') {
+		    self.position += 1;
+		}
+		match digits.parse::<i64>() {
+		    Ok(n) => Token::Int(n),
+		    Err(_) => {
+			return self.error(start, "invalid integer");
+		    }
+		}
+	    },
+	    c if c.is_ascii_alphabetic() || c == '_' => {
+		while let Some(d) = self.peek_char() {
+		    if !(d.is_ascii_alphanumeric() || d == '_') {
+			break;
+		    }
+		    self.position += 1;
+		}
+		Token::Ident(self.source[start..self.position].to_string())
+	    },
+	    _ => {
+		return self.error(start, &format!("unexpected character '{}'", c));
+	    }
+	};
+
+	Ok(Some((start, token)))
+    }
+}
+
+// nesting deeper than this is not written by NumPy, and would overflow the stack
+const MAX_DEPTH:usize = 32;
+
+struct Parser<'a> {
+    tokenizer: Tokenizer<'a>,
+    lookahead: Option<(usize, Token)>,
+    // number of enclosing brackets of the value being parsed
+    depth: usize
+}
+
+impl<'a> Parser<'a> {
+    fn new(source:&'a str) -> Result<Parser<'a>,HeaderError> {
+	let mut tokenizer = Tokenizer::new(source);
+	let lookahead = tokenizer.next_token()?;
+	Ok(Parser { tokenizer: tokenizer, lookahead: lookahead, depth: 0 })
+    }
+
+    fn position(&self) -> usize {
+	match self.lookahead {
+	    Some((pos, _)) => pos,
+	    None => self.tokenizer.source.len()
+	}
+    }
+
+    fn advance(&mut self) -> Result<Option<(usize, Token)>,HeaderError> {
+	let next = self.tokenizer.next_token()?;
+	Ok(std::mem::replace(&mut self.lookahead, next))
+    }
+
+    fn expect(&mut self, expected:Token, name:&str) -> Result<(),HeaderError> {
+	match self.lookahead {
+	    Some((_, ref t)) if *t == expected => {
+		self.advance()?;
+		Ok(())
+	    },
+	    _ => self.tokenizer.error(self.position(), &format!("expected '{}'", name))
+	}
+    }
+
+    // parses comma separated values until the closing token.
+    // returns the values and whether a comma was found.
+    fn parse_sequence(&mut self, close:Token, name:&str) -> Result<(Vec<PyValue>, bool),HeaderError> {
+	let mut values:Vec<PyValue> = Vec::new();
+	let mut has_comma = false;
+	loop {
+	    if let Some((_, ref t)) = self.lookahead {
+		if *t == close {
+		    self.advance()?;
+		    return Ok((values, has_comma));
+		}
+	    }
+	    values.push(self.parse_value()?);
+	    match self.lookahead {
+		Some((_, Token::Comma)) => {
+		    has_comma = true;
+		    self.advance()?;
+		},
+		Some((_, ref t)) if *t == close => (),
+		_ => {
+		    return self.tokenizer.error(self.position(), &format!("expected ',' or '{}'", name));
+		}
+	    }
+	}
+    }
+
+    // parses entries after '{' with the positions of each key and value.
+    fn parse_dict_entries(&mut self) -> Result<Vec<(usize, PyValue, usize, PyValue)>,HeaderError> {
+	let mut entries:Vec<(usize, PyValue, usize, PyValue)> = Vec::new();
+	loop {
+	    if let Some((_, Token::RightCurlyBracket)) = self.lookahead {
+		self.advance()?;
+		return Ok(entries);
+	    }
+	    let key_position = self.position();
+	    let key = self.parse_value()?;
+	    self.expect(Token::Colon, ":")?;
+	    let value_position = self.position();
+	    let value = self.parse_value()?;
+	    entries.push((key_position, key, value_position, value));
+	    match self.lookahead {
+		Some((_, Token::Comma)) => {
+		    self.advance()?;
+		},
+		Some((_, Token::RightCurlyBracket)) => (),
+		_ => {
+		    return self.tokenizer.error(self.position(), "expected ',' or '}'");
+		}
+	    }
+	}
+    }
+
+    fn parse_value(&mut self) -> Result<PyValue,HeaderError> {
+	let position = self.position();
+	let token = match self.advance()? {
+	    Some((_, token)) => token,
+	    None => {
+		return self.tokenizer.error(position, "unexpected end of header");
+	    }
+	};
+
+	let opens = matches!(token, Token::LeftParenthesis | Token::LeftSquareBracket | Token::LeftCurlyBracket);
+	if opens {
+	    if self.depth >= MAX_DEPTH {
+		return self.tokenizer.error(position, "too deeply nested");
+	    }
+	    self.depth += 1;
+	}
+	let value = match token {
+	    Token::Str(s) => Ok(PyValue::Str(s)),
+	    Token::Int(n) => Ok(PyValue::Int(n)),
+	    Token::Ident(ident) => {
+		match ident.as_str() {
+		    "True" => Ok(PyValue::Bool(true)),
+		    "False" => Ok(PyValue::Bool(false)),
+		    "None" => Ok(PyValue::None),
+		    _ => self.tokenizer.error(position, &format!("unknown identifier '{}'", ident))
+		}
+	    },
+	    Token::LeftParenthesis => {
+		let (values, has_comma) = self.parse_sequence(Token::RightParenthesis, ")")?;
+		if values.len() == 1 && !has_comma {
+		    // (x) is not a tuple, just a parenthesized value
+		    Ok(values.into_iter().next().unwrap())
+		}
+		else {
+		    Ok(PyValue::Tuple(values))
+		}
+	    },
+	    Token::LeftSquareBracket => {
+		let (values, _) = self.parse_sequence(Token::RightSquareBracket, "]")?;
+		Ok(PyValue::List(values))
+	    },
+	    Token::LeftCurlyBracket => {
+		let entries = self.parse_dict_entries()?;
+		Ok(PyValue::Dict(entries.into_iter().map(|(_, key, _, value)| (key, value)).collect()))
+	    },
+	    _ => self.tokenizer.error(position, "unexpected token")
+	};
+	if opens {
+	    self.depth -= 1;
+	}
+	value
+    }
+}
+
+/// Parses a python literal. Trailing text other than whitespace is an error.
+#[cfg(test)]
+pub fn parse_literal(source:&str) -> Result<PyValue,HeaderError> {
+    let mut parser = Parser::new(source)?;
+    let value = parser.parse_value()?;
+    if parser.lookahead.is_some() {
+	return parser.tokenizer.error(parser.position(), "unexpected text after literal");
+    }
+    Ok(value)
+}
+
+/// Parses the header dict of npy format into typed values.
+pub fn parse_header(source:&str) -> Result<NpyHeader,HeaderError> {
+    let mut parser = Parser::new(source)?;
+    parser.expect(Token::LeftCurlyBracket, "{")?;
+    let entries = parser.parse_dict_entries()?;
+    if parser.lookahead.is_some() {
+	return parser.tokenizer.error(parser.position(), "unexpected text after header");
+    }
+
+    let mut descr:Option<PyValue> = None;
+    let mut fortran_order:Option<bool> = None;
+    let mut shape:Option<Vec<usize>> = None;
+
+    for (key_position, key, position, value) in entries {
+	let key = match key {
+	    PyValue::Str(key) => key,
+	    _ => {
+		return Err(HeaderError { position: key_position, message: "header key is not a string".to_string() });
+	    }
+	};
+	let duplicated = match key.as_str() {
+	    "descr" => {
+		match value {
+		    PyValue::Str(_) | PyValue::List(_) => descr.replace(value).is_some(),
+		    _ => {
+			return Err(HeaderError { position: position, message: "descr is not a string or a list".to_string() });
+		    }
+		}
+	    },
+	    "fortran_order" => {
+		match value {
+		    PyValue::Bool(b) => fortran_order.replace(b).is_some(),
+		    _ => {
+			return Err(HeaderError { position: position, message: "fortran_order is not a bool".to_string() });
+		    }
+		}
+	    },
+	    "shape" => {
+		let dims = match value {
+		    PyValue::Tuple(dims) => dims,
+		    _ => {
+			return Err(HeaderError { position: position, message: "shape is not a tuple".to_string() });
+		    }
+		};
+		let mut s:Vec<usize> = Vec::with_capacity(dims.len());
+		for dim in dims {
+		    match dim {
+			PyValue::Int(n) if n >= 0 => s.push(n as usize),
+			_ => {
+			    return Err(HeaderError { position: position, message: "invalid shape value".to_string() });
+			}
+		    }
+		}
+		shape.replace(s).is_some()
+	    },
+	    _ => {
+		return Err(HeaderError { position: key_position, message: format!("unknown key '{}'", key) });
+	    }
+	};
+	if duplicated {
+	    return Err(HeaderError { position: key_position, message: format!("duplicated key '{}'", key) });
+	}
+    }
+
+    match (descr, fortran_order, shape) {
+	(Some(descr), Some(fortran_order), Some(shape)) => {
+	    Ok(NpyHeader {
+		descr: descr,
+		fortran_order: fortran_order,
+		shape: shape
+	    })
+	},
+	(None, _, _) => Err(HeaderError { position: 0, message: "not found descr key".to_string() }),
+	(_, None, _) => Err(HeaderError { position: 0, message: "not found fortran_order key".to_string() }),
+	(_, _, None) => Err(HeaderError { position: 0, message: "not found shape key".to_string() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // headers as written by several NumPy versions, including padding.
+    const CORPUS:&[(&str, &str, bool, &[usize])] = &[
+	("{'descr': '<f4', 'fortran_order': False, 'shape': (784, 100), }                                                          \n",
+	 "<f4", false, &[784, 100]),
+	("{'descr': '<f8', 'fortran_order': False, 'shape': (3,), }                                                                \n",
+	 "<f8", false, &[3]),
+	("{'descr': '<i8', 'fortran_order': True, 'shape': (2, 3, 4), }                                                           \n",
+	 "<i8", true, &[2, 3, 4]),
+	("{'descr': '|b1', 'fortran_order': False, 'shape': (), }                                                                 \n",
+	 "|b1", false, &[]),
+	("{'descr': '|u1', 'fortran_order': False, 'shape': (0,), }                                                               \n",
+	 "|u1", false, &[0]),
+	// python2 writes long integers with L suffix
+	("{'descr': '<i4', 'fortran_order': False, 'shape': (10L, 20L), }                                                         \n",
+	 "<i4", false, &[10, 20]),
+	// without trailing comma
+	("{'descr': '>f2', 'fortran_order': False, 'shape': (5, 6)}\n", ">f2", false, &[5, 6]),
+	// keys in other order and double quoted
+	("{\"shape\": (1, 2, 3, 4), \"fortran_order\": False, \"descr\": \"<c16\"}", "<c16", false, &[1, 2, 3, 4]),
+    ];
+
+    #[test]
+    fn parse_corpus() {
+	for (source, descr, fortran_order, shape) in CORPUS {
+	    let header = parse_header(source).unwrap_or_else(|e| panic!("{} : {}", source, e));
+	    assert_eq!(header.descr, PyValue::Str(descr.to_string()));
+	    assert_eq!(header.fortran_order, *fortran_order);
+	    assert_eq!(header.shape, shape.to_vec());
+	}
+    }
+
+    #[test]
+    fn parse_structured_descr() {
+	let source = "{'descr': [('label', '<i8'), ('feature', '<f4', (2, 3)), ('pos', [('x', '<f8'), ('y', '<f8')]), (\"it's\", '|u1')], 'fortran_order': False, 'shape': (7,), }";
+	let header = parse_header(source).unwrap();
+	let fields = match header.descr {
+	    PyValue::List(fields) => fields,
+	    _ => panic!("descr is not a list")
+	};
+	assert_eq!(fields.len(), 4);
+	assert_eq!(fields[1], PyValue::Tuple(vec![PyValue::Str("feature".to_string()),
+						 PyValue::Str("<f4".to_string()),
+						 PyValue::Tuple(vec![PyValue::Int(2), PyValue::Int(3)])]));
+	assert_eq!(fields[2], PyValue::Tuple(vec![PyValue::Str("pos".to_string()),
+						 PyValue::List(vec![
+						     PyValue::Tuple(vec![PyValue::Str("x".to_string()), PyValue::Str("<f8".to_string())]),
+						     PyValue::Tuple(vec![PyValue::Str("y".to_string()), PyValue::Str("<f8".to_string())])])]));
+	assert_eq!(fields[3], PyValue::Tuple(vec![PyValue::Str("it's".to_string()), PyValue::Str("|u1".to_string())]));
+	assert_eq!(header.shape, vec![7]);
+    }
+
+    #[test]
+    fn parse_literals() {
+	assert_eq!(parse_literal("'{,:}'"), Ok(PyValue::Str("{,:}".to_string())));
+	assert_eq!(parse_literal("'a\\'b\\x41\\u3042'"), Ok(PyValue::Str("a'bA\u{3042}".to_string())));
+	assert_eq!(parse_literal("(1)"), Ok(PyValue::Int(1)));
+	assert_eq!(parse_literal("(-1,)"), Ok(PyValue::Tuple(vec![PyValue::Int(-1)])));
+	assert_eq!(parse_literal("((), [None])"), Ok(PyValue::Tuple(vec![PyValue::Tuple(vec![]),
+									 PyValue::List(vec![PyValue::None])])));
+    }
+
+    #[test]
+    fn error_positions() {
+	let cases:&[(&str, usize)] = &[
+	    ("{'descr': '<f4' 'fortran_order': False, 'shape': (), }", 16),
+	    ("{'descr': '<f4', 'fortran_order': false, 'shape': (), }", 34),
+	    ("{'descr': '<f4', 'fortran_order': False, 'shape': (1, 2, }", 57),
+	    ("{'descr': '<f4, 'fortran_order': False, 'shape': (), }", 17),
+	    ("{'descr': '<f4', 'fortran_order': False, 'shape': (), } x", 56),
+	    ("{'descr': '<f4', 'fortran_order': False, 'shape': (-1,), }", 50),
+	    ("{'descr': '<f4', 'fortran_order': False, 'shape': (), 'extra': 1}", 54),
+	];
+	for (source, position) in cases {
+	    match parse_header(source) {
+		Ok(_) => panic!("{} should be error", source),
+		Err(e) => assert_eq!(e.position, *position, "{} : {}", source, e)
+	    }
+	}
+	assert!(parse_header("{'descr': '<f4', 'shape': (), }").is_err());
+	assert!(parse_header("{'descr': '<f4', 'descr': '<f4', 'fortran_order': False, 'shape': (), }").is_err());
+    }
+
+    #[test]
+    fn never_panics_on_broken_headers() {
+	// truncation and byte substitution of every corpus entry must end in Ok or Err.
+	let replacements = ['{', '}', '(', ')', '[', ']', '\'', '"', ',', ':', '\\', 'L', '-', ' ', '\u{3042}'];
+	for (source, _, _, _) in CORPUS {
+	    for (i, _) in source.char_indices() {
+		let _ = parse_header(&source[..i]);
+		for r in replacements.iter() {
+		    let mut mutated = String::from(&source[..i]);
+		    mutated.push(*r);
+		    mutated.push_str(&source[i+1..]);
+		    let _ = parse_header(&mutated);
+		}
+	    }
+	}
+
+	// deep nesting is an error at the first bracket over the limit, not a stack overflow.
+	let nested = format!("{{'descr': {}", "(".repeat(65535));
+	let err = parse_header(&nested).unwrap_err();
+	assert_eq!(err.position, 10 + MAX_DEPTH);
+	let nested = format!("{{'descr': {}'<f4'{}", "[".repeat(65535), "]".repeat(65535));
+	assert!(parse_header(&nested).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use serde::Serialize;
+
+use numpy_read::{Descr, NpyArray, NpyData, NpyError, NpyFormat, NpyStream};
+use numpy_read::{ReadOptions, read_npy_with_format, read_npz_with_format, stream_array_name};
+use numpy_read::stats::{self, Summary};
+
+#[derive(Serialize)]
+pub struct ValuesInfo {
+    // "<array>/<field>" for fields of structured array
+    pub name: String,
+    // descr of the values as written in npy header, e.g. "<f4"
+    pub dtype: String,
+    pub shape: Vec<usize>,
+    // None for complex values
+    pub summary: Option<Summary>
+}
+
+#[derive(Serialize)]
+pub struct ArrayInfo {
+    pub name: String,
+    pub version: String,
+    pub descr: String,
+    pub fortran_order: bool,
+    pub shape: Vec<usize>,
+    pub header_bytes: usize,
+    pub data_bytes: usize,
+    pub values: Vec<ValuesInfo>
+}
+
+#[derive(Serialize)]
+pub struct FileInfo {
+    pub file: String,
+    pub file_bytes: u64,
+    pub arrays: Vec<ArrayInfo>
+}
+
+// fields of structured array are decoded in the order of the fields of descr
+fn collect_values(name:&str, descr:&Descr, array:&NpyArray, bins:usize, values:&mut Vec<ValuesInfo>) {
+    if let (NpyData::Record(fields), Descr::Record(field_descrs, _)) = (&array.data, descr) {
+	for ((field_name, field), field_descr) in fields.iter().zip(field_descrs.iter()) {
+	    collect_values(&format!("{}/{}", name, field_name), &field_descr.descr, field, bins, values);
+	}
+	return;
+    }
+
+    values.push(ValuesInfo {
+	name: name.to_string(),
+	dtype: descr.to_string(),
+	shape: array.shape.clone(),
+	summary: array.to_f64().map(|v| stats::summarize(&v, bins))
+    });
+}
+
+fn array_info(name:&str, format:&NpyFormat, array:&NpyArray, bins:usize) -> ArrayInfo {
+    let mut values:Vec<ValuesInfo> = Vec::new();
+    collect_values(name, &format.descr, array, bins, &mut values);
+
+    ArrayInfo {
+	name: name.to_string(),
+	version: format.version.to_string(),
+	descr: format.descr.to_string(),
+	fortran_order: format.fortran_order,
+	shape: format.shape.clone(),
+	header_bytes: format.header_size,
+	data_bytes: format.data_size(),
+	values: values
+    }
+}
+
+// arrays concatenated in the standard input. it is read to the end to know the size.
+fn inspect_stdin(bins:usize, options:&ReadOptions) -> Result<FileInfo,NpyError> {
+    let mut bytes:Vec<u8> = Vec::new();
+    io::stdin().lock().read_to_end(&mut bytes)?;
+
+    let mut arrays:Vec<ArrayInfo> = Vec::new();
+    for (i, result) in NpyStream::with_options(&bytes[..], options).enumerate() {
+	let (format, array) = result?;
+	arrays.push(array_info(&stream_array_name("stdin", i), &format, &array, bins));
+    }
+
+    Ok(FileInfo {
+	file: "-".to_string(),
+	file_bytes: bytes.len() as u64,
+	arrays: arrays
+    })
+}
+
+/// Reads a npy or npz file. "-" is npy arrays in the standard input.
+pub fn inspect(file:&str, bins:usize, options:&ReadOptions) -> Result<FileInfo,NpyError> {
+    if file == "-" {
+	return inspect_stdin(bins, options);
+    }
+    let file_bytes = fs::metadata(file)?.len();
+    let file_path = Path::new(file);
+
+    let arrays = if file_path.extension().map_or(false, |ext| ext == "npz") {
+	read_npz_with_format(file, options)?.iter().map(|(name, format, array)| {
+	    array_info(name, format, array, bins)
+	}).collect()
+    }
+    else {
+	let (format, array) = read_npy_with_format(file, options)?;
+	let stem = file_path.file_stem().unwrap().to_string_lossy().to_string();
+	vec![array_info(&stem, &format, &array, bins)]
+    };
+
+    Ok(FileInfo {
+	file: file.to_string(),
+	file_bytes: file_bytes,
+	arrays: arrays
+    })
+}
+
+fn opt_str(value:Option<f64>) -> String {
+    match value {
+	Some(v) => format!("{}", v),
+	None => "-".to_string()
+    }
+}
+
+pub fn print_text(info:&FileInfo) {
+    println!("{} ({} bytes)", info.file, info.file_bytes);
+    for array in info.arrays.iter() {
+	println!("  {}: version {}, descr {}, {} order, shape {:?}",
+		 array.name, array.version, array.descr,
+		 if array.fortran_order { "Fortran" } else { "C" }, array.shape);
+	println!("    header {} bytes, data {} bytes", array.header_bytes, array.data_bytes);
+	for values in array.values.iter() {
+	    println!("    {} {} {:?}", values.name, values.dtype, values.shape);
+	    let summary = match values.summary {
+		Some(ref summary) => summary,
+		None => {
+		    println!("      no statistics for complex values");
+		    continue;
+		}
+	    };
+	    println!("      count {} nan {} inf {}", summary.count, summary.nan_count, summary.inf_count);
+	    println!("      min {} max {} mean {} std {}",
+		     opt_str(summary.min), opt_str(summary.max), opt_str(summary.mean), opt_str(summary.std));
+	    if let Some(ref histogram) = summary.histogram {
+		for (i, count) in histogram.counts.iter().enumerate() {
+		    let close = if i == histogram.counts.len()-1 { ']' } else { ')' };
+		    println!("      [{:.6e}, {:.6e}{} {}", histogram.edges[i], histogram.edges[i+1], close, count);
+		}
+	    }
+	}
+    }
+}
@@ -0,0 +1,1085 @@
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+use std::io::prelude::*;
+use std::io::{BufReader, ErrorKind};
+use byteorder::{ByteOrder, LittleEndian};
+use half::f16;
+use num_complex::Complex;
+
+mod error;
+mod header;
+mod mmap;
+mod stream;
+mod writer;
+pub mod hdf5_io;
+pub mod stats;
+#[cfg(feature = "tensor")]
+mod tensor;
+#[cfg(test)]
+mod test_util;
+pub use error::NpyError;
+use header::PyValue;
+pub use mmap::{NpyElement, NpyMmap};
+pub use stream::{NpyStream, open_npy_stream, stream_array_name};
+pub use writer::{NpyVersion, write_npy, write_npy_to, write_npz};
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Endian {
+    Little, Big, NotApplicable
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum ValueType {
+    Bool,
+    UInt8, Int8,
+    UInt16, Int16,
+    UInt32, Int32,
+    UInt64, Int64,
+    Float16, Float32, Float64,
+    Complex64, Complex128
+}
+
+impl ValueType {
+    pub fn size(&self) -> usize {
+	match self {
+	    ValueType::Bool | ValueType::UInt8 | ValueType::Int8 => 1,
+	    ValueType::UInt16 | ValueType::Int16 | ValueType::Float16 => 2,
+	    ValueType::UInt32 | ValueType::Int32 | ValueType::Float32 => 4,
+	    ValueType::UInt64 | ValueType::Int64 | ValueType::Float64 | ValueType::Complex64 => 8,
+	    ValueType::Complex128 => 16
+	}
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum Descr {
+    Scalar(Endian, ValueType),
+    // fields and size of one record including padding
+    Record(Vec<RecordField>, usize)
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct RecordField {
+    pub name: String,
+    pub descr: Descr,
+    // shape of sub array. empty for a scalar field
+    pub shape: Vec<usize>,
+    pub offset: usize
+}
+
+impl Descr {
+    pub fn size(&self) -> usize {
+	match self {
+	    Descr::Scalar(_, value_type) => value_type.size(),
+	    Descr::Record(_, size) => *size
+	}
+    }
+}
+
+// same notation as the descr in npy header
+impl fmt::Display for Descr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    Descr::Scalar(endian, value_type) => {
+		let byte_order = match endian {
+		    Endian::Little => '<',
+		    Endian::Big => '>',
+		    Endian::NotApplicable => '|'
+		};
+		let type_code = match value_type {
+		    ValueType::Bool => "b1",
+		    ValueType::UInt8 => "u1",
+		    ValueType::Int8 => "i1",
+		    ValueType::UInt16 => "u2",
+		    ValueType::Int16 => "i2",
+		    ValueType::UInt32 => "u4",
+		    ValueType::Int32 => "i4",
+		    ValueType::UInt64 => "u8",
+		    ValueType::Int64 => "i8",
+		    ValueType::Float16 => "f2",
+		    ValueType::Float32 => "f4",
+		    ValueType::Float64 => "f8",
+		    ValueType::Complex64 => "c8",
+		    ValueType::Complex128 => "c16"
+		};
+		write!(f, "{}{}", byte_order, type_code)
+	    },
+	    Descr::Record(fields, size) => {
+		// gaps are written as unnamed void fields like numpy, so that offsets and the itemsize are kept
+		let mut items:Vec<String> = Vec::with_capacity(fields.len());
+		let mut offset = 0;
+		for field in fields {
+		    if field.offset > offset {
+			items.push(format!("('', '|V{}')", field.offset - offset));
+		    }
+		    let descr = match field.descr {
+			Descr::Scalar(_, _) => writer::py_str(&field.descr.to_string()),
+			Descr::Record(_, _) => field.descr.to_string()
+		    };
+		    if field.shape.is_empty() {
+			items.push(format!("({}, {})", writer::py_str(&field.name), descr));
+		    }
+		    else {
+			items.push(format!("({}, {}, {})", writer::py_str(&field.name), descr, writer::shape_str(&field.shape)));
+		    }
+		    offset = field.offset + field.descr.size()*field.shape.iter().product::<usize>();
+		}
+		if *size > offset {
+		    items.push(format!("('', '|V{}')", size - offset));
+		}
+		write!(f, "[{}]", items.join(", "))
+	    }
+	}
+    }
+}
+
+#[derive(Debug)]
+pub struct NpyFormat {
+    pub version: NpyVersion,
+    // bytes before values, that is magic, version, header length and header
+    pub header_size: usize,
+    pub descr : Descr,
+    pub fortran_order: bool,
+    pub shape: Vec<usize>
+}
+
+/// Options for reading npy files.
+#[derive(Debug,Clone,Copy,Default)]
+pub struct ReadOptions {
+    // accepts headers without newline, with other padding or not aligned to 64 bytes,
+    // as written by old writers
+    pub lenient: bool,
+    // values in Fortran order are read by read_value_as_stored
+    pub as_stored: bool
+}
+
+impl NpyFormat {
+    /// Bytes of the values following the header. None when the size does not fit in isize,
+    /// which no allocation or seek can handle.
+    pub fn checked_data_size(&self) -> Option<usize> {
+	self.shape.iter().try_fold(self.descr.size(), |size, &dim| size.checked_mul(dim))
+	    .filter(|&size| size <= isize::MAX as usize)
+    }
+
+    /// Bytes of the values following the header, saturated at usize::MAX.
+    /// Formats read by read_npy_format never saturate, since such shapes are rejected.
+    pub fn data_size(&self) -> usize {
+	self.checked_data_size().unwrap_or(usize::MAX)
+    }
+}
+
+#[derive(Debug,PartialEq)]
+pub enum NpyData {
+    Bool(Box<[bool]>),
+    UInt8(Box<[u8]>), Int8(Box<[i8]>),
+    UInt16(Box<[u16]>), Int16(Box<[i16]>),
+    UInt32(Box<[u32]>), Int32(Box<[i32]>),
+    UInt64(Box<[u64]>), Int64(Box<[i64]>),
+    Float16(Box<[f16]>), Float32(Box<[f32]>), Float64(Box<[f64]>),
+    Complex64(Box<[Complex<f32>]>), Complex128(Box<[Complex<f64>]>),
+    // one array per field. the shape of each is the record shape followed by the field shape
+    Record(Vec<(String, NpyArray)>)
+}
+
+macro_rules! dispatch_npy_data {
+    ($data:expr, $values:ident => $body:expr, $fields:ident => $record_body:expr) => {
+	match $data {
+	    $crate::NpyData::Bool($values) => $body,
+	    $crate::NpyData::UInt8($values) => $body,
+	    $crate::NpyData::Int8($values) => $body,
+	    $crate::NpyData::UInt16($values) => $body,
+	    $crate::NpyData::Int16($values) => $body,
+	    $crate::NpyData::UInt32($values) => $body,
+	    $crate::NpyData::Int32($values) => $body,
+	    $crate::NpyData::UInt64($values) => $body,
+	    $crate::NpyData::Int64($values) => $body,
+	    $crate::NpyData::Float16($values) => $body,
+	    $crate::NpyData::Float32($values) => $body,
+	    $crate::NpyData::Float64($values) => $body,
+	    $crate::NpyData::Complex64($values) => $body,
+	    $crate::NpyData::Complex128($values) => $body,
+	    $crate::NpyData::Record($fields) => $record_body
+	}
+    }
+}
+pub(crate) use dispatch_npy_data;
+
+#[derive(Debug,PartialEq)]
+pub struct NpyArray {
+    pub shape: Vec<usize>,
+    pub data: NpyData
+}
+
+impl NpyArray {
+    /// Returns None for structured arrays.
+    pub fn value_type(&self) -> Option<ValueType> {
+	let value_type = match self.data {
+	    NpyData::Bool(_) => ValueType::Bool,
+	    NpyData::UInt8(_) => ValueType::UInt8,
+	    NpyData::Int8(_) => ValueType::Int8,
+	    NpyData::UInt16(_) => ValueType::UInt16,
+	    NpyData::Int16(_) => ValueType::Int16,
+	    NpyData::UInt32(_) => ValueType::UInt32,
+	    NpyData::Int32(_) => ValueType::Int32,
+	    NpyData::UInt64(_) => ValueType::UInt64,
+	    NpyData::Int64(_) => ValueType::Int64,
+	    NpyData::Float16(_) => ValueType::Float16,
+	    NpyData::Float32(_) => ValueType::Float32,
+	    NpyData::Float64(_) => ValueType::Float64,
+	    NpyData::Complex64(_) => ValueType::Complex64,
+	    NpyData::Complex128(_) => ValueType::Complex128,
+	    NpyData::Record(_) => {
+		return None;
+	    }
+	};
+	Some(value_type)
+    }
+
+    pub fn len(&self) -> usize {
+	self.shape.iter().product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.len() == 0
+    }
+
+    /// Converts real values to f64. Returns None for complex and structured arrays.
+    /// 64bit integers beyond 2^53 are rounded.
+    pub fn to_f64(&self) -> Option<Box<[f64]>> {
+	let values:Vec<f64> = match &self.data {
+	    NpyData::Bool(v) => v.iter().map(|&x| if x { 1.0 } else { 0.0 }).collect(),
+	    NpyData::UInt8(v) => v.iter().map(|&x| x as f64).collect(),
+	    NpyData::Int8(v) => v.iter().map(|&x| x as f64).collect(),
+	    NpyData::UInt16(v) => v.iter().map(|&x| x as f64).collect(),
+	    NpyData::Int16(v) => v.iter().map(|&x| x as f64).collect(),
+	    NpyData::UInt32(v) => v.iter().map(|&x| x as f64).collect(),
+	    NpyData::Int32(v) => v.iter().map(|&x| x as f64).collect(),
+	    NpyData::UInt64(v) => v.iter().map(|&x| x as f64).collect(),
+	    NpyData::Int64(v) => v.iter().map(|&x| x as f64).collect(),
+	    NpyData::Float16(v) => v.iter().map(|&x| x.to_f64()).collect(),
+	    NpyData::Float32(v) => v.iter().map(|&x| x as f64).collect(),
+	    NpyData::Float64(v) => v.to_vec(),
+	    NpyData::Complex64(_) | NpyData::Complex128(_) | NpyData::Record(_) => {
+		return None;
+	    }
+	};
+	Some(values.into_boxed_slice())
+    }
+
+    /// Converts real values to f32. Returns None for complex and structured arrays.
+    pub fn to_f32(&self) -> Option<Box<[f32]>> {
+	if let NpyData::Float32(v) = &self.data {
+	    return Some(v.clone());
+	}
+	self.to_f64().map(|v| v.iter().map(|&x| x as f32).collect())
+    }
+
+    /// Borrows the values when the array is stored as f32.
+    pub fn as_f32(&self) -> Option<&[f32]> {
+	match &self.data {
+	    NpyData::Float32(v) => Some(v),
+	    _ => None
+	}
+    }
+
+    /// Borrows the values when the array is stored as f64.
+    pub fn as_f64(&self) -> Option<&[f64]> {
+	match &self.data {
+	    NpyData::Float64(v) => Some(v),
+	    _ => None
+	}
+    }
+}
+
+fn parse_descr(descr_str:&str) -> Result<(Endian, ValueType),NpyError> {
+
+    let mut chars = descr_str.chars();
+    let endian = match chars.next() {
+	Some('<') => Endian::Little,
+	Some('>') => Endian::Big,
+	Some('|') => Endian::NotApplicable,
+	Some('=') => {
+	    if cfg!(target_endian = "little") {
+		Endian::Little
+	    }
+	    else {
+		Endian::Big
+	    }
+	},
+	_ => {
+	    return Err(NpyError::InvalidDescr(format!("invalid byte order in descr '{}'", descr_str)));
+	}
+    };
+
+    let value_type = match chars.as_str() {
+	"b1" | "?" => ValueType::Bool,
+	"u1" => ValueType::UInt8,
+	"i1" => ValueType::Int8,
+	"u2" => ValueType::UInt16,
+	"i2" => ValueType::Int16,
+	"u4" => ValueType::UInt32,
+	"i4" => ValueType::Int32,
+	"u8" => ValueType::UInt64,
+	"i8" => ValueType::Int64,
+	"f2" => ValueType::Float16,
+	"f4" => ValueType::Float32,
+	"f8" => ValueType::Float64,
+	"c8" => ValueType::Complex64,
+	"c16" => ValueType::Complex128,
+	_ => {
+	    return Err(NpyError::UnsupportedDtype(descr_str.to_string()));
+	}
+    };
+
+    // '|' means byte order is not relevant, which only holds for single byte types.
+    if endian == Endian::NotApplicable && value_type.size() != 1 {
+	return Err(NpyError::InvalidDescr(format!("byte order is required for descr '{}'", descr_str)));
+    }
+
+    Ok((endian, value_type))
+}
+
+fn parse_shape_value(value:&PyValue) -> Option<Vec<usize>> {
+    match value {
+	PyValue::Int(n) if *n >= 0 => Some(vec![*n as usize]),
+	PyValue::Tuple(dims) => {
+	    dims.iter().map(|dim| {
+		match dim {
+		    PyValue::Int(n) if *n >= 0 => Some(*n as usize),
+		    _ => None
+		}
+	    }).collect()
+	},
+	_ => None
+    }
+}
+
+// structured descr is a list of (name, descr) or (name, descr, shape).
+fn parse_record_descr(fields:&[PyValue]) -> Result<Descr,NpyError> {
+
+    let invalid = |field:&PyValue| -> NpyError {
+	NpyError::InvalidDescr(format!("invalid field in structured descr {:?}", field))
+    };
+
+    let mut record_fields:Vec<RecordField> = Vec::new();
+    let mut offset:usize = 0;
+
+    for field in fields {
+	let items = match field {
+	    PyValue::Tuple(items) if items.len() == 2 || items.len() == 3 => items,
+	    _ => {
+		return Err(invalid(field));
+	    }
+	};
+
+	// name may be given with its title as (title, name)
+	let name = match &items[0] {
+	    PyValue::Str(name) => name.clone(),
+	    PyValue::Tuple(pair) if pair.len() == 2 => {
+		match &pair[1] {
+		    PyValue::Str(name) => name.clone(),
+		    _ => {
+			return Err(invalid(field));
+		    }
+		}
+	    },
+	    _ => {
+		return Err(invalid(field));
+	    }
+	};
+
+	let shape = match items.get(2) {
+	    Some(value) => {
+		match parse_shape_value(value) {
+		    Some(shape) => shape,
+		    None => {
+			return Err(invalid(field));
+		    }
+		}
+	    },
+	    None => Vec::new()
+	};
+	let num_of_elements:usize = shape.iter().product();
+
+	let descr = match &items[1] {
+	    PyValue::Str(type_str) => {
+		// unnamed void fields are padding inserted by align=True
+		if name.is_empty() && type_str.starts_with("|V") {
+		    match type_str[2..].parse::<usize>() {
+			Ok(size) => {
+			    offset += size*num_of_elements;
+			    continue;
+			},
+			Err(_) => {
+			    return Err(invalid(field));
+			}
+		    }
+		}
+		let (endian, value_type) = parse_descr(type_str)?;
+		Descr::Scalar(endian, value_type)
+	    },
+	    PyValue::List(sub_fields) => parse_record_descr(sub_fields)?,
+	    _ => {
+		return Err(invalid(field));
+	    }
+	};
+
+	let size = descr.size()*num_of_elements;
+	record_fields.push(RecordField {
+	    name: name,
+	    descr: descr,
+	    shape: shape,
+	    offset: offset
+	});
+	offset += size;
+    }
+
+    Ok(Descr::Record(record_fields, offset))
+}
+
+fn parse_npy_format(format_str: String, version:NpyVersion, header_size:usize) -> Result<NpyFormat,NpyError> {
+
+    let header = header::parse_header(&format_str)?;
+
+    let descr = match header.descr {
+	PyValue::Str(ref descr) => {
+	    let (endian, value_type) = parse_descr(descr)?;
+	    Descr::Scalar(endian, value_type)
+	},
+	PyValue::List(ref fields) => parse_record_descr(fields)?,
+	_ => {
+	    return Err(NpyError::InvalidDescr(format!("{:?} is not descr", header.descr)));
+	}
+    };
+
+    let format = NpyFormat {
+	version: version,
+	header_size: header_size,
+	descr: descr,
+	fortran_order: header.fortran_order,
+	shape: header.shape
+    };
+    if format.checked_data_size().is_none() {
+	return Err(NpyError::Header {
+	    offset: format_str.find("shape").unwrap_or(0),
+	    message: format!("shape {:?} is too large", format.shape)
+	});
+    }
+    Ok(format)
+}
+
+// same as read_exact, but reports how many bytes are read when the stream ends.
+pub(crate) fn read_full<R:Read>(f:&mut R, buffer:&mut [u8]) -> Result<(),NpyError> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+	match f.read(&mut buffer[filled..]) {
+	    Ok(0) => {
+		return Err(NpyError::Truncated { expected: buffer.len(), actual: filled });
+	    },
+	    Ok(readsize) => {
+		filled += readsize;
+	    },
+	    Err(err) if err.kind() == ErrorKind::Interrupted => (),
+	    Err(err) => {
+		return Err(NpyError::Io(err));
+	    }
+	}
+    }
+    Ok(())
+}
+
+// reads size bytes. the buffer grows as bytes arrive, so a short stream which declares
+// a large shape is reported as Truncated without allocating the declared size.
+pub(crate) fn read_sized<R:Read>(f:&mut R, size:usize) -> Result<Vec<u8>,NpyError> {
+    let mut bytes:Vec<u8> = Vec::new();
+    f.take(size as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < size {
+	return Err(NpyError::Truncated { expected: size, actual: bytes.len() });
+    }
+    Ok(bytes)
+}
+
+fn read_magic<R:Read>(f:&mut R) -> Result<(),NpyError>{
+    let mut magic = [0u8;6];
+    read_full(f, &mut magic)?;
+    if b"\x93NUMPY" != &magic {
+	return Err(NpyError::BadMagic);
+    }
+
+    Ok(())
+}
+
+fn read_header<R:Read>(f:&mut R) -> Result<(NpyVersion, usize),NpyError> {
+    let mut version = [0u8;2];
+    read_full(f, &mut version)?;
+
+    let version = match version {
+	[1,0] => NpyVersion::V1,
+	[2,0] => NpyVersion::V2,
+	[3,0] => NpyVersion::V3,
+	_ => {
+	    return Err(NpyError::UnsupportedVersion(version[0], version[1]));
+	}
+    };
+
+    let header_length = if version == NpyVersion::V1 {
+	let mut header_length = [0u8;2];
+	read_full(f, &mut header_length)?;
+	LittleEndian::read_u16(&header_length) as usize
+    }
+    else {
+	let mut header_length = [0u8;4];
+	read_full(f, &mut header_length)?;
+	LittleEndian::read_u32(&header_length) as usize
+    };
+
+    Ok((version, header_length))
+}
+
+// the header is the dict padded by spaces and terminated by newline,
+// so that values start at a multiple of 64 bytes.
+fn check_padding(format_str:&str, header_size:usize) -> Result<(),NpyError> {
+    let dict = match format_str.strip_suffix('\n') {
+	Some(padded) => padded.trim_end_matches(' '),
+	None => {
+	    return Err(NpyError::Header { offset: format_str.len(), message: "header does not end with newline".to_string() });
+	}
+    };
+    if !dict.ends_with('}') {
+	let offset = dict.char_indices().last().map_or(0, |(offset, _)| offset);
+	return Err(NpyError::Header { offset: offset, message: "header is not padded by spaces".to_string() });
+    }
+    if header_size % 64 != 0 {
+	return Err(NpyError::Header {
+	    offset: format_str.len(),
+	    message: format!("values are not aligned to 64 bytes. header is {} bytes", header_size)
+	});
+    }
+    Ok(())
+}
+
+fn read_format<R:Read>(f:&mut R, version:NpyVersion, header_length:usize, options:&ReadOptions) -> Result<NpyFormat,NpyError> {
+    // the whole header is read before parsing, so that a truncated header is reported as is
+    let mut buffer = vec![0u8; header_length];
+    read_full(f, &mut buffer)?;
+
+    // the header is latin1 in version 1.0 and 2.0, and utf-8 in version 3.0
+    let format_str = if version == NpyVersion::V3 {
+	match String::from_utf8(buffer) {
+	    Ok(format_str) => format_str,
+	    Err(err) => {
+		return Err(NpyError::Header {
+		    offset: err.utf8_error().valid_up_to(),
+		    message: "header is not utf-8".to_string()
+		});
+	    }
+	}
+    }
+    else {
+	buffer.iter().map(|&b| b as char).collect()
+    };
+
+    let preamble_size = if version == NpyVersion::V1 { 10 } else { 12 };
+    let header_size = preamble_size + header_length;
+    if options.lenient {
+	// some writers pad the header by NUL
+	let dict = format_str.trim_end_matches(|c:char| c == '\0' || c.is_whitespace()).to_string();
+	parse_npy_format(dict, version, header_size)
+    }
+    else {
+	check_padding(&format_str, header_size)?;
+	parse_npy_format(format_str, version, header_size)
+    }
+}
+
+/// Reads magic, version and format, then leaves the reader at the beginning of values.
+/// The padding and alignment of the header are checked strictly.
+pub fn read_npy_format<R:Read>(f:&mut R) -> Result<NpyFormat,NpyError> {
+    read_npy_format_with(f, &ReadOptions::default())
+}
+
+/// Same as read_npy_format, but the header is checked according to the options.
+pub fn read_npy_format_with<R:Read>(f:&mut R, options:&ReadOptions) -> Result<NpyFormat,NpyError> {
+    read_magic(f)?;
+    let (version, header_length) = read_header(f)?;
+    read_format(f, version, header_length, options)
+}
+
+fn read_array<R:Read>(f:&mut R) -> Result<NpyArray,NpyError> {
+    let format = read_npy_format(f)?;
+    read_value(f, &format)
+}
+
+macro_rules! from_bytes {
+    ($t:ty, $raw:expr, $endian:expr) => {
+	match $endian {
+	    Endian::Big => <$t>::from_be_bytes($raw.try_into().unwrap()),
+	    _ => <$t>::from_le_bytes($raw.try_into().unwrap())
+	}
+    }
+}
+
+// position of the e-th element is
+// (e / sub_count)*record_size + offset + (e % sub_count)*element_size
+pub(crate) struct ElementLayout {
+    pub record_size: usize,
+    pub offset: usize,
+    pub sub_count: usize,
+    pub element_size: usize
+}
+
+fn decode_elements<T, F>(bytes:&[u8], count:usize, layout:&ElementLayout, endian:Endian, decode:F) -> Box<[T]>
+where F: Fn(&[u8], Endian) -> T {
+    (0..count).map(|e| {
+	let pos = (e / layout.sub_count)*layout.record_size + layout.offset + (e % layout.sub_count)*layout.element_size;
+	decode(&bytes[pos..pos+layout.element_size], endian)
+    }).collect()
+}
+
+pub(crate) fn decode_data(bytes:&[u8], shape:&[usize], descr:&Descr, layout:&ElementLayout) -> Result<NpyData,NpyError> {
+
+    let count:usize = shape.iter().product();
+
+    let (endian, value_type) = match descr {
+	Descr::Scalar(endian, value_type) => (*endian, *value_type),
+	Descr::Record(fields, _) => {
+	    if layout.sub_count != 1 {
+		return Err(NpyError::Unsupported("not support sub array of structured type".to_string()));
+	    }
+	    let mut arrays:Vec<(String, NpyArray)> = Vec::with_capacity(fields.len());
+	    for field in fields {
+		let field_layout = ElementLayout {
+		    record_size: layout.record_size,
+		    offset: layout.offset + field.offset,
+		    sub_count: field.shape.iter().product(),
+		    element_size: field.descr.size()
+		};
+		let field_shape:Vec<usize> = shape.iter().chain(field.shape.iter()).cloned().collect();
+		let field_data = decode_data(bytes, &field_shape, &field.descr, &field_layout)?;
+		arrays.push((field.name.clone(), NpyArray {
+		    shape: field_shape,
+		    data: field_data
+		}));
+	    }
+	    return Ok(NpyData::Record(arrays));
+	}
+    };
+
+    let data = match value_type {
+	ValueType::Bool => NpyData::Bool(decode_elements(bytes, count, layout, endian, |raw, _| raw[0] != 0)),
+	ValueType::UInt8 => NpyData::UInt8(decode_elements(bytes, count, layout, endian, |raw, _| raw[0])),
+	ValueType::Int8 => NpyData::Int8(decode_elements(bytes, count, layout, endian, |raw, _| raw[0] as i8)),
+	ValueType::UInt16 => NpyData::UInt16(decode_elements(bytes, count, layout, endian, |raw, e| from_bytes!(u16, raw, e))),
+	ValueType::Int16 => NpyData::Int16(decode_elements(bytes, count, layout, endian, |raw, e| from_bytes!(i16, raw, e))),
+	ValueType::UInt32 => NpyData::UInt32(decode_elements(bytes, count, layout, endian, |raw, e| from_bytes!(u32, raw, e))),
+	ValueType::Int32 => NpyData::Int32(decode_elements(bytes, count, layout, endian, |raw, e| from_bytes!(i32, raw, e))),
+	ValueType::UInt64 => NpyData::UInt64(decode_elements(bytes, count, layout, endian, |raw, e| from_bytes!(u64, raw, e))),
+	ValueType::Int64 => NpyData::Int64(decode_elements(bytes, count, layout, endian, |raw, e| from_bytes!(i64, raw, e))),
+	ValueType::Float16 => NpyData::Float16(decode_elements(bytes, count, layout, endian, |raw, e| f16::from_bits(from_bytes!(u16, raw, e)))),
+	ValueType::Float32 => NpyData::Float32(decode_elements(bytes, count, layout, endian, |raw, e| from_bytes!(f32, raw, e))),
+	ValueType::Float64 => NpyData::Float64(decode_elements(bytes, count, layout, endian, |raw, e| from_bytes!(f64, raw, e))),
+	ValueType::Complex64 => NpyData::Complex64(decode_elements(bytes, count, layout, endian, |raw, e| {
+	    Complex::new(from_bytes!(f32, raw[0..4], e), from_bytes!(f32, raw[4..8], e))
+	})),
+	ValueType::Complex128 => NpyData::Complex128(decode_elements(bytes, count, layout, endian, |raw, e| {
+	    Complex::new(from_bytes!(f64, raw[0..8], e), from_bytes!(f64, raw[8..16], e))
+	}))
+    };
+
+    Ok(data)
+}
+
+// rearranges items stored in Fortran order into C order.
+// the item at the index (i0, i1, ..., in) is at i0 + d0*(i1 + d1*(i2 + ...)) in Fortran order.
+pub(crate) fn fortran_to_c_order(bytes:Vec<u8>, shape:&[usize], item_size:usize) -> Vec<u8> {
+    if shape.len() <= 1 {
+	return bytes;
+    }
+
+    // strides in items of each axis in Fortran order
+    let mut strides = vec![1usize; shape.len()];
+    for axis in 1..shape.len() {
+	strides[axis] = strides[axis-1]*shape[axis-1];
+    }
+
+    let num_of_items:usize = shape.iter().product();
+    let mut reordered = vec![0u8; bytes.len()];
+    let mut index = vec![0usize; shape.len()];
+    let mut src = 0;
+    for dst in 0..num_of_items {
+	reordered[dst*item_size..(dst+1)*item_size].copy_from_slice(&bytes[src*item_size..(src+1)*item_size]);
+
+	// next index in C order, where the last axis changes fastest
+	for axis in (0..shape.len()).rev() {
+	    index[axis] += 1;
+	    src += strides[axis];
+	    if index[axis] < shape[axis] {
+		break;
+	    }
+	    src -= strides[axis]*shape[axis];
+	    index[axis] = 0;
+	}
+    }
+    reordered
+}
+
+pub(crate) fn read_value_in_order<R:Read>(f:&mut R, format:&NpyFormat, as_stored:bool) -> Result<NpyArray,NpyError> {
+
+    let data_size = match format.checked_data_size() {
+	Some(data_size) => data_size,
+	None => {
+	    return Err(NpyError::Header { offset: 0, message: format!("shape {:?} is too large", format.shape) });
+	}
+    };
+    let item_size = format.descr.size();
+    let mut bytes = read_sized(f, data_size)?;
+
+    let mut shape = format.shape.clone();
+    if format.fortran_order {
+	if as_stored {
+	    // values in Fortran order are the transpose in C order
+	    shape.reverse();
+	}
+	else {
+	    bytes = fortran_to_c_order(bytes, &format.shape, item_size);
+	}
+    }
+
+    let layout = ElementLayout {
+	record_size: item_size,
+	offset: 0,
+	sub_count: 1,
+	element_size: item_size
+    };
+    let data = decode_data(&bytes, &shape, &format.descr, &layout)?;
+
+    Ok(NpyArray {
+	shape: shape,
+	data: data
+    })
+}
+
+/// Reads values described by the format, which is read by read_npy_format.
+/// Values in Fortran order are reordered into C order.
+pub fn read_value<R:Read>(f:&mut R, format:&NpyFormat) -> Result<NpyArray,NpyError> {
+    read_value_in_order(f, format, false)
+}
+
+/// Reads values without reordering. An array in Fortran order is returned as its transpose,
+/// that is the shape is reversed and the values are in the order of the file.
+pub fn read_value_as_stored<R:Read>(f:&mut R, format:&NpyFormat) -> Result<NpyArray,NpyError> {
+    read_value_in_order(f, format, true)
+}
+
+/// Reads the first array of a npy stream. Values are converted into C order.
+/// NpyStream reads all arrays when several arrays are concatenated.
+pub fn read_npy_from<R:Read>(mut reader:R) -> Result<NpyArray,NpyError> {
+    read_array(&mut reader)
+}
+
+pub fn read_npy<P:AsRef<Path>>(path:P) -> Result<NpyArray,NpyError> {
+    read_npy_from(BufReader::new(File::open(path)?))
+}
+
+/// Same as read_npy, but also returns the format read from the header.
+pub fn read_npy_with_format<P:AsRef<Path>>(path:P, options:&ReadOptions) -> Result<(NpyFormat, NpyArray),NpyError> {
+    let mut f = BufReader::new(File::open(path)?);
+    let format = read_npy_format_with(&mut f, options)?;
+    let array = read_value_in_order(&mut f, &format, options.as_stored)?;
+    Ok((format, array))
+}
+
+/// Reads every npy member of a npz archive. Names are the member names without ".npy".
+pub fn read_npz<P:AsRef<Path>>(path:P) -> Result<Vec<(String, NpyArray)>,NpyError> {
+    let arrays = read_npz_with_format(path, &ReadOptions::default())?;
+    Ok(arrays.into_iter().map(|(name, _, array)| (name, array)).collect())
+}
+
+/// Same as read_npz, but also returns the format of each member.
+pub fn read_npz_with_format<P:AsRef<Path>>(path:P, options:&ReadOptions) -> Result<Vec<(String, NpyFormat, NpyArray)>,NpyError> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+
+    // np.savez stores each array as "<name>.npy", either stored or deflated.
+    let mut arrays:Vec<(String, NpyFormat, NpyArray)> = Vec::new();
+    for i in 0..archive.len() {
+	let entry = archive.by_index(i)?;
+	let name = match entry.name().strip_suffix(".npy") {
+	    Some(name) => name.to_string(),
+	    None => {
+		continue;
+	    }
+	};
+	let mut reader = BufReader::new(entry);
+	let format = read_npy_format_with(&mut reader, options)?;
+	let array = read_value_in_order(&mut reader, &format, options.as_stored)?;
+	arrays.push((name, format, array));
+    }
+
+    Ok(arrays)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::test_util::{i64_bytes, npy_bytes, npy_raw};
+
+    // np.asfortranarray(np.arange(24).reshape(2, 3, 4)).ravel('K')
+    const FORTRAN_2X3X4:[i64;24] = [0, 12, 4, 16, 8, 20, 1, 13, 5, 17, 9, 21, 2, 14, 6, 18, 10, 22, 3, 15, 7, 19, 11, 23];
+
+    #[test]
+    fn huge_shape() {
+	// the product of the shape overflows usize
+	let bytes = npy_bytes("'<i8'", false, "(4294967296, 4294967296)", &[]);
+	match read_npy_from(Cursor::new(bytes)) {
+	    Err(NpyError::Header { message, .. }) => assert!(message.contains("too large"), "{}", message),
+	    other => panic!("unexpected {:?}", other)
+	}
+	// 1 TiB is declared, but only 2 values follow
+	let bytes = npy_bytes("'<i8'", false, "(137438953472,)", &i64_bytes(&[1, 2]));
+	match read_npy_from(Cursor::new(bytes)) {
+	    Err(NpyError::Truncated { expected, actual }) => assert_eq!((expected, actual), (1 << 40, 16)),
+	    other => panic!("unexpected {:?}", other)
+	}
+    }
+
+    #[test]
+    fn fortran_order_3d() {
+	let bytes = npy_bytes("'<i8'", true, "(2, 3, 4)", &i64_bytes(&FORTRAN_2X3X4));
+	let array = read_npy_from(Cursor::new(bytes)).unwrap();
+	assert_eq!(array.shape, vec![2, 3, 4]);
+	assert_eq!(array.data, NpyData::Int64((0..24).collect::<Vec<i64>>().into_boxed_slice()));
+    }
+
+    #[test]
+    fn fortran_order_as_stored() {
+	let mut f = Cursor::new(npy_bytes("'<i8'", true, "(2, 3, 4)", &i64_bytes(&FORTRAN_2X3X4)));
+	let format = read_npy_format(&mut f).unwrap();
+	assert!(format.fortran_order);
+	let array = read_value_as_stored(&mut f, &format).unwrap();
+	assert_eq!(array.shape, vec![4, 3, 2]);
+	assert_eq!(array.data, NpyData::Int64(FORTRAN_2X3X4.to_vec().into_boxed_slice()));
+    }
+
+    #[test]
+    fn fortran_order_other_ranks() {
+	// np.asfortranarray(np.arange(6).reshape(2, 3))
+	let bytes = npy_bytes("'<i8'", true, "(2, 3)", &i64_bytes(&[0, 3, 1, 4, 2, 5]));
+	let array = read_npy_from(Cursor::new(bytes)).unwrap();
+	assert_eq!(array.data, NpyData::Int64(vec![0, 1, 2, 3, 4, 5].into_boxed_slice()));
+
+	// np.asfortranarray(np.arange(16).reshape(2, 2, 2, 2))
+	let stored = [0, 8, 4, 12, 2, 10, 6, 14, 1, 9, 5, 13, 3, 11, 7, 15];
+	let bytes = npy_bytes("'<i8'", true, "(2, 2, 2, 2)", &i64_bytes(&stored));
+	let array = read_npy_from(Cursor::new(bytes)).unwrap();
+	assert_eq!(array.shape, vec![2, 2, 2, 2]);
+	assert_eq!(array.data, NpyData::Int64((0..16).collect::<Vec<i64>>().into_boxed_slice()));
+
+	// 1-D and 0-D arrays are the same in both orders
+	let bytes = npy_bytes("'<i8'", true, "(3,)", &i64_bytes(&[7, 8, 9]));
+	let array = read_npy_from(Cursor::new(bytes)).unwrap();
+	assert_eq!(array.data, NpyData::Int64(vec![7, 8, 9].into_boxed_slice()));
+	let bytes = npy_bytes("'<i8'", true, "()", &i64_bytes(&[7]));
+	let array = read_npy_from(Cursor::new(bytes)).unwrap();
+	assert_eq!(array.shape, Vec::<usize>::new());
+	assert_eq!(array.data, NpyData::Int64(vec![7].into_boxed_slice()));
+    }
+
+    #[test]
+    fn fortran_order_record() {
+	// records of (a, b) = (10*i, i) for np.arange(4).reshape(2, 2) in Fortran order
+	let data:Vec<u8> = [0i16, 2, 1, 3].iter().flat_map(|i| {
+	    let mut record = (10*i).to_le_bytes().to_vec();
+	    record.push(*i as u8);
+	    record
+	}).collect();
+	let bytes = npy_bytes("[('a', '<i2'), ('b', '|u1')]", true, "(2, 2)", &data);
+	let array = read_npy_from(Cursor::new(bytes)).unwrap();
+	assert_eq!(array.shape, vec![2, 2]);
+	let fields = match array.data {
+	    NpyData::Record(fields) => fields,
+	    _ => panic!("not a record")
+	};
+	assert_eq!(fields[0].0, "a");
+	assert_eq!(fields[0].1.data, NpyData::Int16(vec![0, 10, 20, 30].into_boxed_slice()));
+	assert_eq!(fields[1].0, "b");
+	assert_eq!(fields[1].1.data, NpyData::UInt8(vec![0, 1, 2, 3].into_boxed_slice()));
+    }
+
+    fn record_fields(array:NpyArray) -> Vec<(String, NpyArray)> {
+	match array.data {
+	    NpyData::Record(fields) => fields,
+	    _ => panic!("not a record")
+	}
+    }
+
+    #[test]
+    fn structured_sub_array() {
+	// np.array([(1, (0.5, 1.5), True), (-2, (2.5, 3.5), False)],
+	//          dtype=[('id', '<i4'), ('pos', '<f8', (2,)), ('flag', '?')])
+	let mut data:Vec<u8> = Vec::new();
+	for (id, pos, flag) in [(1i32, [0.5f64, 1.5], 1u8), (-2, [2.5, 3.5], 0)] {
+	    data.extend_from_slice(&id.to_le_bytes());
+	    pos.iter().for_each(|p| data.extend_from_slice(&p.to_le_bytes()));
+	    data.push(flag);
+	}
+	let bytes = npy_bytes("[('id', '<i4'), ('pos', '<f8', (2,)), ('flag', '|b1')]", false, "(2,)", &data);
+	let mut f = Cursor::new(bytes);
+	let format = read_npy_format(&mut f).unwrap();
+	assert_eq!(format.descr.size(), 21);
+	assert_eq!(format.data_size(), 42);
+	let fields = record_fields(read_value_as_stored(&mut f, &format).unwrap());
+	assert_eq!(fields.iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>(), vec!["id", "pos", "flag"]);
+	assert_eq!(fields[0].1.data, NpyData::Int32(vec![1, -2].into_boxed_slice()));
+	// the shape of a field is the record shape followed by the sub array shape
+	assert_eq!(fields[1].1.shape, vec![2, 2]);
+	assert_eq!(fields[1].1.data, NpyData::Float64(vec![0.5, 1.5, 2.5, 3.5].into_boxed_slice()));
+	assert_eq!(fields[2].1.data, NpyData::Bool(vec![true, false].into_boxed_slice()));
+    }
+
+    #[test]
+    fn structured_padding_and_titles() {
+	// align=True inserts unnamed void padding, and a field may be given as ((title, name), descr)
+	let data = [7, 0, 0, 0, 0, 0, 1, 0, 9, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe];
+	let bytes = npy_bytes("[('a', '|u1'), ('', '|V3'), (('title', 'b'), '>i4')]", false, "(2,)", &data);
+	let mut f = Cursor::new(bytes);
+	let format = read_npy_format(&mut f).unwrap();
+	assert_eq!(format.descr.size(), 8);
+	match format.descr {
+	    Descr::Record(ref fields, _) => assert_eq!(fields.iter().map(|field| field.offset).collect::<Vec<usize>>(), vec![0, 4]),
+	    _ => panic!("not a record")
+	}
+	let fields = record_fields(read_value_as_stored(&mut f, &format).unwrap());
+	assert_eq!(fields.len(), 2);
+	assert_eq!(fields[0].1.data, NpyData::UInt8(vec![7, 9].into_boxed_slice()));
+	assert_eq!(fields[1].0, "b");
+	assert_eq!(fields[1].1.data, NpyData::Int32(vec![256, -2].into_boxed_slice()));
+    }
+
+    #[test]
+    fn structured_nested() {
+	let mut data:Vec<u8> = Vec::new();
+	for (x, y, w) in [(1i16, -1i16, 0.25f32), (2, -2, 0.75)] {
+	    data.extend_from_slice(&x.to_le_bytes());
+	    data.extend_from_slice(&y.to_le_bytes());
+	    data.extend_from_slice(&w.to_le_bytes());
+	}
+	let bytes = npy_bytes("[('p', [('x', '<i2'), ('y', '<i2')]), ('w', '<f4')]", false, "(2,)", &data);
+	let mut fields = record_fields(read_npy_from(Cursor::new(bytes)).unwrap());
+	assert_eq!(fields[1].1.data, NpyData::Float32(vec![0.25, 0.75].into_boxed_slice()));
+	let point = record_fields(fields.remove(0).1);
+	assert_eq!(point[0].1.data, NpyData::Int16(vec![1, 2].into_boxed_slice()));
+	assert_eq!(point[1].1.data, NpyData::Int16(vec![-1, -2].into_boxed_slice()));
+    }
+
+    #[test]
+    fn structured_errors() {
+	let bytes = npy_bytes("[('a',)]", false, "(1,)", &[0]);
+	assert!(matches!(read_npy_from(Cursor::new(bytes)), Err(NpyError::InvalidDescr(_))));
+	let bytes = npy_bytes("[('a', '<x4')]", false, "(1,)", &[0; 4]);
+	assert!(read_npy_from(Cursor::new(bytes)).is_err());
+	// sub array of a structured field
+	let bytes = npy_bytes("[('p', [('x', '|u1')], (2,))]", false, "(1,)", &[1, 2]);
+	assert!(matches!(read_npy_from(Cursor::new(bytes)), Err(NpyError::Unsupported(_))));
+    }
+
+    fn descr_from_str(descr:&str) -> Descr {
+	match header::parse_literal(descr).unwrap() {
+	    PyValue::Str(s) => {
+		let (endian, value_type) = parse_descr(&s).unwrap();
+		Descr::Scalar(endian, value_type)
+	    },
+	    PyValue::List(fields) => parse_record_descr(&fields).unwrap(),
+	    value => panic!("{:?} is not descr", value)
+	}
+    }
+
+    #[test]
+    fn descr_round_trip() {
+	let descrs = [
+	    "[('id', '<i4'), ('pos', '<f8', (2,)), ('flag', '|b1')]",
+	    "[('a', '|u1'), ('', '|V3'), ('b', '>i4'), ('', '|V8')]",
+	    "[('p', [('x', '<i2'), ('', '|V2'), ('y', '<i4', (2, 3))]), ('w', '<f4', (1, 2))]",
+	    "[('it\\'s', '<c16')]"
+	];
+	for source in descrs.iter() {
+	    let descr = descr_from_str(source);
+	    assert_eq!(&descr.to_string(), source);
+	    assert_eq!(descr_from_str(&descr.to_string()), descr);
+	}
+	// padding stays in the itemsize
+	assert_eq!(descr_from_str(descrs[1]).size(), 16);
+	assert_eq!(descr_from_str(descrs[2]).size(), 36);
+	// titles are dropped, and the name is kept
+	let descr = descr_from_str("[(('title', 'a'), '<f4')]");
+	assert_eq!(descr.to_string(), "[('a', '<f4')]");
+    }
+
+    fn header_error(bytes:Vec<u8>, options:&ReadOptions) -> Option<String> {
+	match read_npy_format_with(&mut Cursor::new(bytes), options) {
+	    Err(NpyError::Header { message, .. }) => Some(message),
+	    Err(err) => panic!("unexpected error {}", err),
+	    Ok(_) => None
+	}
+    }
+
+    #[test]
+    fn header_padding() {
+	let strict = ReadOptions::default();
+	let lenient = ReadOptions { lenient: true, as_stored: false };
+	let dict = "{'descr': '<i8', 'fortran_order': False, 'shape': (3,), }";
+
+	// aligned to 16 bytes as old numpy
+	let header = format!("{:<1$}\n", dict, 80 - 10 - 1);
+	assert!(header_error(npy_raw(1, header.as_bytes(), &[]), &strict).unwrap().contains("aligned"));
+	assert_eq!(header_error(npy_raw(1, header.as_bytes(), &[]), &lenient), None);
+
+	// without newline
+	let header = format!("{:<1$}", dict, 128 - 10);
+	assert!(header_error(npy_raw(1, header.as_bytes(), &[]), &strict).unwrap().contains("newline"));
+	assert_eq!(header_error(npy_raw(1, header.as_bytes(), &[]), &lenient), None);
+
+	// padded by NUL
+	let mut header = dict.as_bytes().to_vec();
+	header.resize(128 - 10 - 1, 0);
+	header.push(b'\n');
+	assert!(header_error(npy_raw(1, &header, &[]), &strict).unwrap().contains("spaces"));
+	assert_eq!(header_error(npy_raw(1, &header, &[]), &lenient), None);
+
+	// header length beyond the end of file
+	let header = format!("{:<1$}\n", dict, 128 - 10 - 1);
+	let mut bytes = npy_raw(1, header.as_bytes(), &[]);
+	bytes.truncate(100);
+	match read_npy_format(&mut Cursor::new(bytes)) {
+	    Err(NpyError::Truncated { expected, actual }) => assert_eq!((expected, actual), (118, 90)),
+	    result => panic!("unexpected result {:?}", result.map(|format| format.shape))
+	}
+    }
+
+    #[test]
+    fn header_encoding() {
+	// field name in utf-8 is valid only in version 3.0
+	let dict = "{'descr': [('\u{6e29}\u{5ea6}', '<i8')], 'fortran_order': False, 'shape': (1,), }";
+	let header = format!("{:<1$}\n", dict, 128 - 12 - 1 - (dict.len() - dict.chars().count()));
+	assert_eq!((12 + header.len()) % 64, 0);
+	let array = read_npy_from(Cursor::new(npy_raw(3, header.as_bytes(), &i64_bytes(&[21])))).unwrap();
+	match array.data {
+	    NpyData::Record(fields) => assert_eq!(fields[0].0, "\u{6e29}\u{5ea6}"),
+	    _ => panic!("not a record")
+	}
+
+	// version 1.0 and 2.0 headers are latin1
+	let dict = "{'descr': [('\u{e9}t\u{e9}', '<i8')], 'fortran_order': False, 'shape': (1,), }";
+	let mut header:Vec<u8> = dict.chars().map(|c| c as u8).collect();
+	while (12 + header.len() + 1) % 64 != 0 {
+	    header.push(b' ');
+	}
+	header.push(b'\n');
+	let array = read_npy_from(Cursor::new(npy_raw(2, &header, &i64_bytes(&[21])))).unwrap();
+	match array.data {
+	    NpyData::Record(fields) => assert_eq!(fields[0].0, "\u{e9}t\u{e9}"),
+	    _ => panic!("not a record")
+	}
+    }
+}
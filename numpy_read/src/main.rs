@@ -1,548 +1,312 @@
 
-use std::{env,mem};
-use std::fs::File;
+use std::process;
 use std::path::Path;
-use std::io::prelude::*;
-use std::io::{BufReader, Read};
-use std::collections::HashMap;
-use byteorder::{ByteOrder, LittleEndian};
 
 use clap::{App,Arg};
 use hdf5;
 
+mod info;
+
+use numpy_read::{NpyError, NpyMmap, NpyVersion, ReadOptions};
+use numpy_read::hdf5_io::{export_hdf5, open_group, stream_to_hdf5, write_array, write_mmap};
+use numpy_read::hdf5_io::{DatasetAttributes, WriteOptions};
+use numpy_read::{open_npy_stream, read_npz_with_format, stream_array_name};
+
 #[allow(dead_code)]
 fn get_hex_rep(byte_array: &[u8]) -> String {
     let build_string_vec: Vec<String> = byte_array.iter().enumerate()
-        .map(|(i, val)| {
-            if i == 7 { format!("{:02x} ", val) }
-            else { format!("{:02x}", val) }
-        }).collect();
+	.map(|(i, val)| {
+	    if i == 7 { format!("{:02x} ", val) }
+	    else { format!("{:02x}", val) }
+	}).collect();
     build_string_vec.join(" ")
 }
 
-#[derive(Debug)]
-enum Endian {
-    Little, Big
-}
-
-#[derive(Debug)]
-enum ValueType {
-    UInt8, Int8,
-    UInt16, Int16,
-    UInt32, Int32,
-    UInt64, Int64,
-    Float32, Float64
+fn exit_with(err:NpyError) -> ! {
+    eprintln!("Error {}", err);
+    process::exit(err.exit_code());
 }
 
-#[derive(Debug)]
-struct NpyFormat {
-    pub value_type : ValueType,
-    pub endian : Endian,
-    pub fortran_order: bool,
-    pub shape: (usize, usize)
-}
-
-#[derive(Debug)]
-struct NpyArray {
-    pub shape: (usize, usize),
-    pub values: Box<[f64]>
-}
-
-fn parse_npy_format(format_str: String) -> Result<NpyFormat,(u32, String)> {
+fn main() {
 
-    #[derive(PartialEq)]
-    enum ParseState {
-	SearchLeftCurlyBracket,
-	SearchKeyQuato,
-	StoreKey,
-	SearchSpliter,
-	SearchValueStr,
-	StoreValue,
-	StoreValueParentheses,
-	SearchComma
+    struct AppContext {
+	weight_files : Vec<String>,
+	output_file : Option<String>,
+	use_mmap : bool,
+	slab_rows : Option<usize>,
+	append : bool,
+	group : Option<String>,
+	write_options : WriteOptions,
+	with_attributes : bool,
+	lenient : bool
     }
 
-    let mut dict:HashMap<String, String> = HashMap::new();
-    {
-	let s = format_str.trim().chars();
-	let mut state: ParseState = ParseState::SearchLeftCurlyBracket;
-	let mut key:Vec<char> = Vec::new();
-	let mut value:Vec<char> = Vec::new();
-
-	//println!("{}", format_str);
+    let app_args = App::new("npy_reader")
+	.version("0.1.0")
+	.args_conflicts_with_subcommands(true)
+	.arg(Arg::new("npy_files")
+	     .help("npy or npz files. - reads npy from the standard input. concatenated arrays are named as <stem>, <stem>_1, ...")
+	     .multiple(true))
+	.arg(Arg::new("output_file")
+	     .short('o')
+	     .long("output_file")
+	     .takes_value(true))
+	.arg(Arg::new("mmap")
+	     .help("map npy files into memory and write values without copying")
+	     .long("mmap")
+	     .takes_value(false))
+	.arg(Arg::new("slab_rows")
+	     .help("write npy files into hdf5 by slabs of the specified rows")
+	     .long("slab_rows")
+	     .takes_value(true)
+	     .conflicts_with("mmap"))
+	.arg(Arg::new("append")
+	     .help("add datasets to the existing output file instead of truncating it")
+	     .short('a')
+	     .long("append")
+	     .takes_value(false))
+	.arg(Arg::new("group")
+	     .help("group path where datasets are written. missing groups are created")
+	     .short('g')
+	     .long("group")
+	     .takes_value(true))
+	.arg(Arg::new("chunk")
+	     .help("chunk shape separated by comma, e.g. 64,784")
+	     .long("chunk")
+	     .takes_value(true))
+	.arg(Arg::new("gzip")
+	     .help("compress datasets by gzip of the level")
+	     .long("gzip")
+	     .takes_value(true)
+	     .possible_values(["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"]))
+	.arg(Arg::new("shuffle")
+	     .help("apply shuffle filter")
+	     .long("shuffle")
+	     .takes_value(false))
+	.arg(Arg::new("attributes")
+	     .help("record source file, dtype and fortran_order as attributes")
+	     .long("attributes")
+	     .takes_value(false))
+	.arg(Arg::new("lenient")
+	     .help("accept npy headers with broken padding or alignment written by old writers")
+	     .long("lenient")
+	     .takes_value(false))
+	.arg(Arg::new("keep_fortran_order")
+	     .help("write fortran order arrays as stored, with the reversed shape and the attribute storage_order=\"F\"")
+	     .long("keep_fortran_order")
+	     .takes_value(false))
+	.subcommand(App::new("export")
+		    .about("export every dataset of hdf5 file to npy files")
+		    .arg(Arg::new("hdf5_file")
+			 .required(true))
+		    .arg(Arg::new("output")
+			 .help("output directory, or npz file when the name ends with .npz")
+			 .short('o')
+			 .long("output")
+			 .takes_value(true)
+			 .required(true))
+		    .arg(Arg::new("npy_version")
+			 .help("npy format version. default is the smallest version which can hold the header")
+			 .long("npy_version")
+			 .takes_value(true)
+			 .possible_values(["1", "2", "3"])))
+	.subcommand(App::new("info")
+		    .about("print header and statistics of npy and npz files")
+		    .arg(Arg::new("files")
+			 .help("npy or npz files. - reads npy arrays from the standard input")
+			 .required(true)
+			 .multiple(true))
+		    .arg(Arg::new("json")
+			 .help("print as json")
+			 .long("json")
+			 .takes_value(false))
+		    .arg(Arg::new("bins")
+			 .help("number of histogram bins")
+			 .long("bins")
+			 .takes_value(true)
+			 .default_value("10"))
+		.arg(Arg::new("lenient")
+		     .help("accept npy headers with broken padding or alignment written by old writers")
+		     .long("lenient")
+		     .takes_value(false)));
 
-	for c in s {
-	    match c {
-		'{' => {
-		    if state == ParseState::SearchLeftCurlyBracket {
-			state = ParseState::SearchKeyQuato;
-		    }
-		    else if state == ParseState::StoreValue {
-			value.push(c);
-		    }
-		    else if state == ParseState::StoreKey {
-			key.push(c);
-		    }
-		    else {
-			return Err((4,"Invalid format string".to_string()));
-		    }
-		},
-		'}' => {
-		    if state ==  ParseState::SearchComma {
-			break;
-		    }
-		},
-		'\'' => {
-		    if state == ParseState::SearchKeyQuato {
-			key.clear();
-			state = ParseState::StoreKey;
-		    }
-		    else if state == ParseState::StoreKey {
-			state = ParseState::SearchSpliter;
-		    }
-		    else if state == ParseState::SearchValueStr {
-			value.push(c);
-			state = ParseState::StoreValue;
-		    }
-		    else if state == ParseState::StoreValue {
-			value.push(c);
-		    }
-		    else {
-			return Err((4,"Invalid format string".to_string()));
-		    }
-		},
-		':' => {
-		    if state == ParseState::SearchSpliter {
-			state = ParseState::SearchValueStr;
-		    }
-		    else if state == ParseState::StoreValue {
-			value.push(c);
-		    }
-		    else if state == ParseState::StoreKey {
-			key.push(c);
-		    }
+    let ctx = match app_args.try_get_matches(){
+	Ok(m) => {
+	    if let Some(("export", export_m)) = m.subcommand() {
+		let version = match export_m.value_of("npy_version") {
+		    Some("1") => Some(NpyVersion::V1),
+		    Some("2") => Some(NpyVersion::V2),
+		    Some("3") => Some(NpyVersion::V3),
+		    _ => None
+		};
+		if let Err(err) = export_hdf5(export_m.value_of("hdf5_file").unwrap(),
+					      export_m.value_of("output").unwrap(),
+					      version) {
+		    exit_with(err);
 		}
-		' ' => {
-		    if state == ParseState::StoreValue {
-			value.push(c);
-		    }
-		    else if state == ParseState::StoreKey {
-			key.push(c);
-		    }
-		},
-		',' => {
-		    if state == ParseState::StoreValue {
-			dict.insert(key.iter().collect::<String>(), value.iter().collect::<String>());
-			state = ParseState::SearchKeyQuato;
-		    }
-		    else if state == ParseState::StoreKey {
-			key.push(c);
-		    }
-		    else if state == ParseState::StoreValueParentheses {
-			value.push(c);
+		return;
+	    }
+	    if let Some(("info", info_m)) = m.subcommand() {
+		let bins = match info_m.value_of("bins").unwrap().parse::<usize>() {
+		    Ok(bins) => bins,
+		    Err(e) => {
+			eprintln!("Error invalid bins {}", e);
+			process::exit(2);
+		    }
+		};
+		let read_options = ReadOptions {
+		    lenient: info_m.contains_id("lenient"),
+		    as_stored: false
+		};
+		let mut infos:Vec<info::FileInfo> = Vec::new();
+		for file in info_m.values_of("files").unwrap() {
+		    match info::inspect(file, bins, &read_options) {
+			Ok(file_info) => infos.push(file_info),
+			Err(err) => {
+			    exit_with(err);
+			}
 		    }
 		}
-		'(' => {
-		    if state == ParseState::StoreKey {
-			key.push(c);
-		    }
-		    else if state == ParseState::SearchValueStr {
-			value.clear();
-			value.push(c);
-			state = ParseState::StoreValueParentheses;
-		    }
+		if info_m.contains_id("json") {
+		    // NaN and Inf are excluded from statistics, so values are always valid json numbers
+		    println!("{}", serde_json::to_string_pretty(&infos).unwrap());
+		}
+		else {
+		    infos.iter().for_each(info::print_text);
+		}
+		return;
+	    }
+	    let files:Vec<&str> = match m.values_of("npy_files") {
+		Some(files) => files.collect(),
+		None => Vec::new()
+	    };
+	    AppContext {
+		weight_files : files.into_iter().map(String::from).collect(),
+		output_file : match m.value_of("output_file") {
+		    Some(str) => Some(str.to_string()),
+		    None => None
 		},
-		')' => {
-		    if state == ParseState::StoreKey {
-			key.push(c);
-		    }
-		    else if state == ParseState::StoreValueParentheses {
-			value.push(c);
-			state = ParseState::StoreValue;
-		    }
+		use_mmap : m.contains_id("mmap"),
+		slab_rows : match m.value_of("slab_rows").map(|rows| rows.parse::<usize>()) {
+		    Some(Ok(rows)) => Some(rows),
+		    Some(Err(e)) => {
+			eprintln!("Error invalid slab_rows {}", e);
+			process::exit(2);
+		    },
+		    None => None
 		},
-		_ => {
-		    if state == ParseState::SearchValueStr {
-			value.clear();
-			value.push(c);
-			state = ParseState::StoreValue;
-		    }
-		    else if state == ParseState::StoreKey {
-			key.push(c);
-		    }
-		    else if state == ParseState::StoreValue {
-			value.push(c);
-		    }
-		    else if state == ParseState::StoreValueParentheses {
-			value.push(c);
-		    }
-		}
+		append : m.contains_id("append"),
+		group : m.value_of("group").map(String::from),
+		write_options : WriteOptions {
+		    chunk : match m.value_of("chunk").map(|chunk| {
+			chunk.split(',').map(|dim| dim.trim().parse::<usize>()).collect::<Result<Vec<usize>,_>>()
+		    }) {
+			Some(Ok(chunk)) => Some(chunk),
+			Some(Err(e)) => {
+			    eprintln!("Error invalid chunk {}", e);
+			    process::exit(2);
+			},
+			None => None
+		    },
+		    gzip : m.value_of("gzip").map(|level| level.parse::<u8>().unwrap()),
+		    shuffle : m.contains_id("shuffle"),
+		    keep_fortran_order : m.contains_id("keep_fortran_order")
+		},
+		with_attributes : m.contains_id("attributes"),
+		lenient : m.contains_id("lenient")
 	    }
+	},
+	Err(e) => {
+	    // prints help and version too
+	    e.exit();
 	}
-    }
-
-    let (endian, value_type) = if let Some(descr) = dict.get("descr") {
-	let descr_str = descr.trim_matches('\'');
-	(if descr_str.chars().nth(0) == Some('<') {
-	    Endian::Little
-	}
-	 else {
-	     Endian::Big
-	 },
-	 if &descr_str[1..] == "f4" {
-	     ValueType::Float32
-	 }
-	 else if &descr_str[1..] == "f8" {
-	     ValueType::Float64
-	 }
-	 else if &descr_str[1..] == "u8" {
-	     ValueType::UInt8
-	 }
-	 else {
-	     ValueType::UInt8
-	 })
-    }
-    else {
-	return Err((5,"not found descr key".to_string()));
-    };
-
-    let fortran_order = if let Some(order) = dict.get("fortran_order") {
-	if order == "True" {
-	    true
-	}
-	else if order == "False" {
-	    false
-	}
-	else {
-	    return Err((5,"not found fortran_order key".to_string()));
-	}
-    }
-    else {
-	return Err((5,"not found fortran order key".to_string()));
     };
 
-    let shape = if let Some(shape_str) = dict.get("shape") {
-	let shape_token:Vec<&str> = shape_str.
-	    trim_start_matches('(').trim_end_matches(')').trim_end_matches(',').split(',').collect::<Vec<&str>>();
-
-	if shape_token.len() == 1 {
-	    match shape_token[0].parse() {
-		Ok(r) => (1, r),
-		Err(_) => {
-		    return Err((5,"invalid shape value".to_string()));
-		}
-	    }
-	}
-	else if shape_token.len() == 2 {
-	    match (shape_token[0].parse(), shape_token[1].parse()) {
-		(Ok(c), Ok(r)) => {
-		    (c, r)
-		}
-		_ => {
-		    return Err((5,"invalid shape value".to_string()));
-		}
-	    }
+    let open_output = |output_file:String| -> Result<hdf5::Group,hdf5::Error> {
+	let h = if ctx.append {
+	    // creates the file when it does not exist
+	    hdf5::File::append(output_file)?
 	}
 	else {
-	    return Err((5,"invalid shape value".to_string()));
-	}
-    }
-    else {
-	return Err((5,"not found shape key".to_string()));
+	    hdf5::File::create(output_file)?
+	};
+	open_group(&h, ctx.group.as_deref().unwrap_or("/"))
     };
-
-    Ok(NpyFormat {
-	value_type: value_type,
-	endian: endian,
-	fortran_order: fortran_order,
-	shape: shape
-    })
-}
-
-fn read_magic(mut f:BufReader<File>) -> Result<NpyArray,(u32,String)>{
-
-    match f.fill_buf() {
-	Ok(b) => {
-	    if b.len() == 0 {
-		return Err((3, "file size is too short".to_string()));
-	    }
-	},
-	Err(err) => {
-	    return Err((2, err.to_string()));
-	}
-    }
-
-    let mut magic:[u8;6] = unsafe{ mem::MaybeUninit::uninit().assume_init() };
-    match f.read(&mut magic) {
-	Ok(readsize) => {
-	    if b"\x93NUMPY" != &magic {
-		return Err((1,"magic is invalid".to_string()));
-	    }
+    let output:Option<hdf5::Group> = match ctx.output_file.clone().map(open_output) {
+	Some(Ok(group)) => Some(group),
+	Some(Err(err)) => {
+	    exit_with(NpyError::Hdf5(err));
 	},
-	Err(err) =>{
-	    println!("Error {}", err);
-	    return Err((2, err.to_string()));
-	}
-    }
-
-    read_header(f)
-
-}
-
-fn read_header(mut f:BufReader<File>) -> Result<NpyArray,(u32,String)> {
-    let mut version = [0u8,2];
-    let mut padding_size:usize = 0;
+	None => None
+    };
+    let read_options = ReadOptions {
+	lenient: ctx.lenient,
+	as_stored: ctx.write_options.keep_fortran_order
+    };
+    let source = |weight_file:&str| -> Option<String> {
+	if ctx.with_attributes { Some(weight_file.to_string()) } else { None }
+    };
 
-    match f.fill_buf() {
-	Ok(b) => {
-	    if b.len() == 0 {
-		return Err((3, "file size is too short".to_string()));
-	    }
-	},
-	Err(err) => {
-	    return Err((2, err.to_string()));
+    for weight_file in ctx.weight_files {
+	let file_path = Path::new(&weight_file);
+	let is_stdin = weight_file == "-";
+	let is_npz = !is_stdin && file_path.extension().map_or(false, |ext| ext == "npz");
+	let stem = if is_stdin {
+	    "stdin".to_string()
 	}
-    }
+	else {
+	    file_path.file_stem().unwrap().to_string_lossy().to_string()
+	};
 
-    match f.read(&mut version) {
-	Ok(readsize) => {
-	    if version == [1,0] {
-		let mut padding_length = [0u8,2];
-		match f.read(&mut padding_length) {
-		    Ok(readsize) => {
-			padding_size = LittleEndian::read_u16(&padding_length) as usize;
-		    },
-		    Err(err) => {
-			println!("Error {}", err);
-			return Err((2, err.to_string()));
-		    }
-		}
+	if !is_npz && (ctx.use_mmap || ctx.slab_rows.is_some()) {
+	    let result = if ctx.use_mmap && is_stdin {
+		Err(NpyError::Unsupported("standard input can not be mapped into memory".to_string()))
 	    }
-	    else if version == [2,0] {
-		let mut padding_length = [0u8,4];
-		match f.read(&mut padding_length) {
-		    Ok(readsize) => {
-			padding_size = LittleEndian::read_u32(&padding_length) as usize;
-		    },
-		    Err(err) => {
-			println!("Error {}", err);
-			return Err((2, err.to_string()));
+	    else if ctx.use_mmap {
+		NpyMmap::open_with(&weight_file, &read_options).and_then(|npy| {
+		    match output {
+			Some(ref output) => write_mmap(output, &stem, &npy, &ctx.write_options, source(&weight_file).as_deref()),
+			None => Ok(())
 		    }
-		}
+		})
 	    }
 	    else {
-		return Err((3,"Unknown format version".to_string()));
-	    }
-	},
-	Err(err) => {
-	    println!("Error {}", err);
-	    return Err((2, err.to_string()));
-	}
-    }
-
-    read_format(f, padding_size)
-
-}
-
-fn read_format(mut f:BufReader<File>, padding_size:usize) -> Result<NpyArray,(u32,String)> {
-
-    match f.fill_buf() {
-	Ok(b) => {
-	    if b.len() == 0 {
-		return Err((3, "file size is too short".to_string()));
-	    }
-	},
-	Err(err) => {
-	    return Err((2, err.to_string()));
-	}
-    };
-
-    let buffer = f.buffer();
-    let npy_format = match parse_npy_format(String::from_utf8(buffer[0..padding_size].to_vec()).unwrap()){
-	Ok(format) => {
-	    format
-	}
-	Err(err) => {
-	    println!("Err {:?}", err);
-	    return Err(err);
-	}
-    };
-    f.consume(padding_size);
-
-    read_value(f, npy_format)
-
-}
-
-fn read_value(mut f:BufReader<File>, format: NpyFormat) -> Result<NpyArray,(u32,String)> {
-
-    let (c,r) = format.shape;
-    let mut values:Vec<f64> = Vec::with_capacity(c*r);
-    let mut read_counter = 0;
-    let (mut i, mut j) = (0,0);
-
-    unsafe { values.set_len(c*r) };
-
-
-    loop {
-	match f.fill_buf() {
-	    Ok(b) => {
-		if b.len() == 0 {
-		    if read_counter == c*r {
-			return Ok(NpyArray {
-			    shape: (c, r),
-			    values: values.into_boxed_slice()
-			});
-		    }
-		    else {
-			return Err((3, "file size is too short".to_string()));
-		    }
-		}
-	    },
-	    Err(err) => {
-		return Err((2, err.to_string()));
-	    }
-	};
-
-	values[i*r+j] = match format.value_type {
-	    ValueType::Float32 => {
-		let mut value_raw = [0u8;4];
-		match f.read(&mut value_raw) {
-		    Ok(readsize) => {
-			match format.endian {
-			    Endian::Little => {
-				f32::from_le_bytes(value_raw) as f64
-			    }
-			    _ => {
-				f32::from_be_bytes(value_raw) as f64
-			    }
-			}
-		    },
-		    Err(err) => {
-			return Err((2, err.to_string()));
-		    }
+		match output {
+		    Some(ref output) => stream_to_hdf5(&weight_file, output, &stem, ctx.slab_rows.unwrap(),
+						       &ctx.write_options, &read_options, ctx.with_attributes),
+		    None => open_npy_stream(&weight_file, &read_options).and_then(|stream| stream.collect::<Result<Vec<_>,_>>()).map(|_| ())
 		}
-	    },
-	    _ => {
-		return Err((8,"not support value format ".to_string()));
+	    };
+	    if let Err(err) = result {
+		exit_with(err);
 	    }
-	};
+	    continue;
+	}
 
-	if format.fortran_order {
-	    // Fortran order
-	    i += 1;
-	    if i >= format.shape.0 {
-		i = 0;
-		j += 1;
-	    }
+	let parsed = if is_npz {
+	    read_npz_with_format(&weight_file, &read_options)
 	}
 	else {
-	    // normal order
-	    j += 1;
-	    if j >= format.shape.1 {
-		j  = 0;
-		i += 1;
-	    }
+	    open_npy_stream(&weight_file, &read_options).and_then(|stream| {
+		stream.enumerate().map(|(index, parsed)| {
+		    parsed.map(|(format, weight_array)| (stream_array_name(&stem, index), format, weight_array))
+		}).collect()
+	    })
 	};
 
-	read_counter += 1;
-    }
-
-}
-
-fn parse_npy(file:&str) ->  Result<NpyArray,(u32,String)> {
-    let mut f = BufReader::new(
-	File::open(&file).expect(format!("Failed to open file. {}", file).as_str())
-    );
-
-    read_magic(f)
-}
-
-fn main() {
-
-    struct AppContext {
-	weight_files : Vec<String>,
-	output_file : Option<String>
-    }
-
-    let app_args = App::new("npy_reader")
-	.version("0.1.0")
-	.arg(Arg::new("npy_files").
-	     multiple(true))
-	.arg(Arg::new("output_file")
-	     .short('o')
-	     .long("output_file")
-	     .takes_value(true));
-
-    let ctx = match app_args.try_get_matches(){
-	Ok(m) => {
-	    let files:Vec<&str> = m.values_of("npy_files").unwrap().collect();
-	    AppContext {
-		weight_files : files.into_iter().map(String::from).collect(),
-		output_file : match m.value_of("output_file") {
-		    Some(str) => Some(str.to_string()),
-		    None => None
-		}
+	let weight_arrays = match parsed {
+	    Ok(weight_arrays) => weight_arrays,
+	    Err(err) => {
+		exit_with(err);
 	    }
-	},
-	Err(e) => {
-	    println!("Error {}", e);
-	    return;
-	}
-    };
-
-    let output:Option<hdf5::File> = if let Some(output_file) = ctx.output_file {
-	if let Ok(h) = hdf5::File::create(output_file) {
-	    Some(h)
-	}
-	else {
-	    None
-	}
-    }
-    else {
-	None
-    };
+	};
 
-    for weight_file in ctx.weight_files {
-	match parse_npy(&weight_file) {
-	    Ok(weight_array) => {
-		/*
-		println!("{} {}", weight_array.shape.0, weight_array.shape.1);
-		println!("{:?}", &weight_array.values[weight_array.shape.1*0+0..weight_array.shape.1*1]);
-		println!("{}", weight_array.values[weight_array.shape.1*0+1]);
-		println!("{}", weight_array.values[weight_array.shape.1*1+0]);
-		println!("{}", weight_array.values[weight_array.shape.1*10+12]);
-		println!("{:?}", &weight_array.values[weight_array.shape.1*783..weight_array.shape.1*784]);
-*/
-		if let Some(ref output) = output {
-		    let file_path = Path::new(&weight_file);
-		    let array = &weight_array.values;
-		    let wrapped_date_set = output.new_dataset::<f32>().shape([weight_array.shape.0, weight_array.shape.1]).create(file_path.file_stem().unwrap().to_str());
-		    if let Ok(data_set) = wrapped_date_set {
-			if let Err(err) = data_set.write_raw(array) {
-			    match err {
-				hdf5::Error::HDF5(es) => {
-				    if let Ok(expanded_es) = es.expand() {
-					eprintln!("{}",expanded_es.description().to_string());
-				    }
-				    else {
-					eprintln!("Internal Error");
-				    }
-				},
-				hdf5::Error::Internal(es) => {
-				    eprintln!("{}",es.to_string());
-				}
-			    }
-			}
-		    }
-		    else {
-			eprintln!("failed to write hdf5 file");
-			return;
-		    }
+	if let Some(ref output) = output {
+	    for (name, format, weight_array) in weight_arrays {
+		let attributes = DatasetAttributes::new(source(&weight_file).as_deref(), &format, &ctx.write_options);
+		if let Err(err) = write_array(output, &name, &weight_array, &ctx.write_options, &attributes) {
+		    exit_with(NpyError::Hdf5(err));
 		}
-	    },
-	    Err((code, message)) => {
-		println!("{} {}", code, message);
-		return;
 	    }
 	}
     }
@@ -0,0 +1,146 @@
+use std::fs::File;
+use std::mem;
+
+use half::f16;
+use memmap2::Mmap;
+use num_complex::Complex;
+
+use crate::{Descr, Endian, ElementLayout, NpyArray, NpyFormat, ReadOptions, ValueType};
+use crate::{decode_data, fortran_to_c_order, read_npy_format_with};
+use crate::error::NpyError;
+
+/// Element types which can be viewed directly on the mapped bytes.
+/// bool is not included since bytes other than 0 and 1 are not valid bool.
+pub unsafe trait NpyElement: Copy {
+    const VALUE_TYPE: ValueType;
+}
+
+unsafe impl NpyElement for u8 { const VALUE_TYPE: ValueType = ValueType::UInt8; }
+unsafe impl NpyElement for i8 { const VALUE_TYPE: ValueType = ValueType::Int8; }
+unsafe impl NpyElement for u16 { const VALUE_TYPE: ValueType = ValueType::UInt16; }
+unsafe impl NpyElement for i16 { const VALUE_TYPE: ValueType = ValueType::Int16; }
+unsafe impl NpyElement for u32 { const VALUE_TYPE: ValueType = ValueType::UInt32; }
+unsafe impl NpyElement for i32 { const VALUE_TYPE: ValueType = ValueType::Int32; }
+unsafe impl NpyElement for u64 { const VALUE_TYPE: ValueType = ValueType::UInt64; }
+unsafe impl NpyElement for i64 { const VALUE_TYPE: ValueType = ValueType::Int64; }
+unsafe impl NpyElement for f16 { const VALUE_TYPE: ValueType = ValueType::Float16; }
+unsafe impl NpyElement for f32 { const VALUE_TYPE: ValueType = ValueType::Float32; }
+unsafe impl NpyElement for f64 { const VALUE_TYPE: ValueType = ValueType::Float64; }
+unsafe impl NpyElement for Complex<f32> { const VALUE_TYPE: ValueType = ValueType::Complex64; }
+unsafe impl NpyElement for Complex<f64> { const VALUE_TYPE: ValueType = ValueType::Complex128; }
+
+/// Memory mapped npy file. The header is validated on open and
+/// the values are read from the mapping on demand.
+pub struct NpyMmap {
+    mmap: Mmap,
+    format: NpyFormat,
+    data_offset: usize
+}
+
+impl NpyMmap {
+    pub fn open(file:&str) -> Result<NpyMmap,NpyError> {
+	NpyMmap::open_with(file, &ReadOptions::default())
+    }
+
+    /// Same as open, but the header is checked according to the options.
+    pub fn open_with(file:&str, options:&ReadOptions) -> Result<NpyMmap,NpyError> {
+	let f = File::open(file)?;
+
+	// the file must not be modified while it is mapped
+	let mmap = unsafe { Mmap::map(&f)? };
+
+	let mut header:&[u8] = &mmap[..];
+	let format = read_npy_format_with(&mut header, options)?;
+	let data_offset = mmap.len() - header.len();
+
+	let data_size = format.data_size();
+	if header.len() < data_size {
+	    return Err(NpyError::Truncated { expected: data_size, actual: header.len() });
+	}
+
+	Ok(NpyMmap {
+	    mmap: mmap,
+	    format: format,
+	    data_offset: data_offset
+	})
+    }
+
+    pub fn format(&self) -> &NpyFormat {
+	&self.format
+    }
+
+    pub fn shape(&self) -> &[usize] {
+	&self.format.shape
+    }
+
+    /// Raw bytes of the values in the order stored in the file.
+    pub fn bytes(&self) -> &[u8] {
+	let data_size = self.format.data_size();
+	&self.mmap[self.data_offset..self.data_offset+data_size]
+    }
+
+    /// Views the values as a typed slice without copying.
+    /// The values are in Fortran order when format().fortran_order is true.
+    pub fn as_slice<T:NpyElement>(&self) -> Result<&[T],NpyError> {
+	match self.format.descr {
+	    Descr::Scalar(endian, value_type) if value_type == T::VALUE_TYPE => {
+		let native = if cfg!(target_endian = "little") { Endian::Little } else { Endian::Big };
+		if endian != native && endian != Endian::NotApplicable {
+		    return Err(NpyError::Unsupported("byte order of values is not native".to_string()));
+		}
+	    },
+	    _ => {
+		return Err(NpyError::Unsupported(format!("values are not {:?}", T::VALUE_TYPE)));
+	    }
+	}
+
+	let bytes = self.bytes();
+	if (bytes.as_ptr() as usize) % mem::align_of::<T>() != 0 {
+	    return Err(NpyError::Unsupported("values are not aligned".to_string()));
+	}
+
+	// length and alignment are checked, and every bit pattern is valid for T.
+	Ok(unsafe {
+	    std::slice::from_raw_parts(bytes.as_ptr() as *const T, bytes.len() / mem::size_of::<T>())
+	})
+    }
+
+    /// Decodes the mapped values into NpyArray in C order.
+    pub fn to_array(&self) -> Result<NpyArray,NpyError> {
+	self.decode(false)
+    }
+
+    /// Decodes the mapped values without reordering. See read_value_as_stored.
+    pub fn to_array_as_stored(&self) -> Result<NpyArray,NpyError> {
+	self.decode(true)
+    }
+
+    fn decode(&self, as_stored:bool) -> Result<NpyArray,NpyError> {
+	let item_size = self.format.descr.size();
+	let mut shape = self.format.shape.clone();
+	let reordered;
+	let bytes = if self.format.fortran_order && !as_stored {
+	    reordered = fortran_to_c_order(self.bytes().to_vec(), &self.format.shape, item_size);
+	    &reordered[..]
+	}
+	else {
+	    if self.format.fortran_order {
+		shape.reverse();
+	    }
+	    self.bytes()
+	};
+
+	let layout = ElementLayout {
+	    record_size: item_size,
+	    offset: 0,
+	    sub_count: 1,
+	    element_size: item_size
+	};
+	let data = decode_data(bytes, &shape, &self.format.descr, &layout)?;
+
+	Ok(NpyArray {
+	    shape: shape,
+	    data: data
+	})
+    }
+}
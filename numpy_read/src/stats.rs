@@ -0,0 +1,75 @@
+use std::cmp;
+
+use serde::Serialize;
+
+/// Histogram of finite values. counts[i] is the number of values in [edges[i], edges[i+1]),
+/// and the last bin also includes the upper edge.
+#[derive(Debug,Clone,Serialize)]
+pub struct Histogram {
+    pub edges: Vec<f64>,
+    pub counts: Vec<usize>
+}
+
+/// Summary statistics of values. NaN and Inf are only counted, and the others are computed
+/// over finite values. They are None when there is no finite value.
+#[derive(Debug,Clone,Serialize)]
+pub struct Summary {
+    pub count: usize,
+    pub nan_count: usize,
+    pub inf_count: usize,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+    // population standard deviation, same as np.std
+    pub std: Option<f64>,
+    pub histogram: Option<Histogram>
+}
+
+pub fn summarize(values:&[f64], bins:usize) -> Summary {
+    let nan_count = values.iter().filter(|x| x.is_nan()).count();
+    let inf_count = values.iter().filter(|x| x.is_infinite()).count();
+    let finite_count = values.len() - nan_count - inf_count;
+    let finite = || values.iter().cloned().filter(|x| x.is_finite());
+
+    if finite_count == 0 {
+	return Summary {
+	    count: values.len(),
+	    nan_count: nan_count,
+	    inf_count: inf_count,
+	    min: None,
+	    max: None,
+	    mean: None,
+	    std: None,
+	    histogram: None
+	};
+    }
+
+    let min = finite().fold(f64::INFINITY, f64::min);
+    let max = finite().fold(f64::NEG_INFINITY, f64::max);
+    let mean = finite().sum::<f64>() / finite_count as f64;
+    let variance = finite().map(|x| (x-mean)*(x-mean)).sum::<f64>() / finite_count as f64;
+
+    let bins = cmp::max(bins, 1);
+    let width = (max - min) / bins as f64;
+    let edges:Vec<f64> = (0..=bins).map(|i| if i == bins { max } else { min + width*i as f64 }).collect();
+    let mut counts = vec![0usize; bins];
+    for x in finite() {
+	// all values fall into the first bin when min == max
+	let i = if width > 0.0 { ((x - min) / width) as usize } else { 0 };
+	counts[cmp::min(i, bins-1)] += 1;
+    }
+
+    Summary {
+	count: values.len(),
+	nan_count: nan_count,
+	inf_count: inf_count,
+	min: Some(min),
+	max: Some(max),
+	mean: Some(mean),
+	std: Some(variance.sqrt()),
+	histogram: Some(Histogram {
+	    edges: edges,
+	    counts: counts
+	})
+    }
+}
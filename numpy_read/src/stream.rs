@@ -0,0 +1,191 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, SeekFrom};
+
+use crate::{NpyArray, NpyFormat, ReadOptions};
+use crate::{read_npy_format_with, read_value_in_order};
+use crate::error::NpyError;
+
+/// Reads npy arrays written one after another into a stream, e.g. a pipe.
+/// The stream ends when no byte is left before the magic of the next array.
+pub struct NpyStream<R:Read> {
+    reader: R,
+    options: ReadOptions,
+    // stops iteration after an error, since the position in the stream is unknown
+    failed: bool
+}
+
+impl<R:Read> NpyStream<R> {
+    pub fn new(reader:R) -> NpyStream<R> {
+	NpyStream::with_options(reader, &ReadOptions::default())
+    }
+
+    pub fn with_options(reader:R, options:&ReadOptions) -> NpyStream<R> {
+	NpyStream {
+	    reader: reader,
+	    options: *options,
+	    failed: false
+	}
+    }
+
+    /// Reads the format of the next array, or returns None at the end of the stream.
+    /// The values must be read by read_value or skipped by skip_value before the next format.
+    pub fn next_format(&mut self) -> Result<Option<NpyFormat>,NpyError> {
+	let mut first = [0u8;1];
+	loop {
+	    match self.reader.read(&mut first) {
+		Ok(0) => {
+		    return Ok(None);
+		},
+		Ok(_) => {
+		    break;
+		},
+		Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+		Err(err) => {
+		    return Err(NpyError::Io(err));
+		}
+	    }
+	}
+	let mut chained = (&first[..]).chain(&mut self.reader);
+	read_npy_format_with(&mut chained, &self.options).map(Some)
+    }
+
+    /// Reads the values of the array whose format is just read.
+    /// Values in Fortran order are not reordered when options.as_stored is true.
+    pub fn read_value(&mut self, format:&NpyFormat) -> Result<NpyArray,NpyError> {
+	read_value_in_order(&mut self.reader, format, self.options.as_stored)
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+	&mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+	self.reader
+    }
+}
+
+impl<R:Read+Seek> NpyStream<R> {
+    /// Skips the values of the array whose format is just read, without reading them.
+    pub fn skip_value(&mut self, format:&NpyFormat) -> Result<(),NpyError> {
+	let end = self.reader.seek(SeekFrom::Current(format.data_size() as i64))?;
+	// seeking beyond the end is not an error, so the size is checked here
+	let size = self.reader.seek(SeekFrom::End(0))?;
+	if end > size {
+	    let actual = format.data_size() - (end - size) as usize;
+	    return Err(NpyError::Truncated { expected: format.data_size(), actual: actual });
+	}
+	self.reader.seek(SeekFrom::Start(end))?;
+	Ok(())
+    }
+}
+
+impl<R:Read> Iterator for NpyStream<R> {
+    type Item = Result<(NpyFormat, NpyArray),NpyError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+	if self.failed {
+	    return None;
+	}
+	let result = match self.next_format() {
+	    Ok(Some(format)) => self.read_value(&format).map(|array| (format, array)),
+	    Ok(None) => {
+		return None;
+	    },
+	    Err(err) => Err(err)
+	};
+	self.failed = result.is_err();
+	Some(result)
+    }
+}
+
+/// Opens a npy file as a stream. "-" is the standard input.
+pub fn open_npy_stream(path:&str, options:&ReadOptions) -> Result<NpyStream<Box<dyn Read>>,NpyError> {
+    let reader:Box<dyn Read> = if path == "-" {
+	Box::new(BufReader::new(io::stdin()))
+    }
+    else {
+	Box::new(BufReader::new(File::open(path)?))
+    };
+    Ok(NpyStream::with_options(reader, options))
+}
+
+/// Dataset name of the index-th array in a stream.
+/// The first array is named as the stream, and the following are suffixed with "_1", "_2", ...
+pub fn stream_array_name(name:&str, index:usize) -> String {
+    if index == 0 {
+	name.to_string()
+    }
+    else {
+	format!("{}_{}", name, index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::NpyData;
+    use crate::test_util::to_bytes;
+
+    // an array of u8 and an array of i64 written one after another
+    fn concatenated() -> (Vec<u8>, Vec<NpyArray>) {
+	let arrays = vec![
+	    NpyArray { shape: vec![2, 3], data: NpyData::UInt8(vec![1, 2, 3, 4, 5, 6].into_boxed_slice()) },
+	    NpyArray { shape: vec![2], data: NpyData::Int64(vec![-1, i64::MAX].into_boxed_slice()) }
+	];
+	let bytes = arrays.iter().flat_map(|array| to_bytes(array, None)).collect();
+	(bytes, arrays)
+    }
+
+    #[test]
+    fn concatenated_arrays() {
+	let (bytes, arrays) = concatenated();
+	let mut stream = NpyStream::new(Cursor::new(bytes));
+	let (format, array) = stream.next().unwrap().unwrap();
+	assert_eq!((format.header_size, format.shape.clone()), (128, vec![2, 3]));
+	assert_eq!(array, arrays[0]);
+	let (format, array) = stream.next().unwrap().unwrap();
+	assert_eq!(format.descr.to_string(), "<i8");
+	assert_eq!(array, arrays[1]);
+	assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn skip_values() {
+	let (bytes, arrays) = concatenated();
+	let mut stream = NpyStream::new(Cursor::new(bytes));
+	let format = stream.next_format().unwrap().unwrap();
+	stream.skip_value(&format).unwrap();
+	let format = stream.next_format().unwrap().unwrap();
+	assert_eq!(stream.read_value(&format).unwrap(), arrays[1]);
+	assert!(stream.next_format().unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_array() {
+	let (mut bytes, arrays) = concatenated();
+	bytes.truncate(bytes.len() - 4);
+	let mut stream = NpyStream::new(Cursor::new(bytes.clone()));
+	assert_eq!(stream.next().unwrap().unwrap().1, arrays[0]);
+	match stream.next() {
+	    Some(Err(NpyError::Truncated { expected: 16, actual: 12 })) => (),
+	    other => panic!("unexpected {:?}", other.map(|result| result.map(|(_, array)| array)))
+	}
+	// the position after an error is unknown, so the iteration ends
+	assert!(stream.next().is_none());
+
+	let mut stream = NpyStream::new(Cursor::new(bytes));
+	stream.next().unwrap().unwrap();
+	let format = stream.next_format().unwrap().unwrap();
+	assert!(matches!(stream.skip_value(&format), Err(NpyError::Truncated { expected: 16, actual: 12 })));
+    }
+
+    #[test]
+    fn array_names() {
+	assert_eq!(stream_array_name("stdin", 0), "stdin");
+	assert_eq!(stream_array_name("stdin", 2), "stdin_2");
+    }
+}
@@ -0,0 +1,60 @@
+use linear_transform::tensor::tensor_base::Tensor;
+
+use crate::NpyArray;
+
+// 0-D array becomes a tensor which has one element.
+fn tensor_shape(shape:&[usize]) -> Vec<usize> {
+    if shape.is_empty() {
+	vec![1]
+    }
+    else {
+	shape.to_vec()
+    }
+}
+
+impl NpyArray {
+    /// Converts real values into Tensor<f32>. Returns None for complex and structured arrays.
+    pub fn to_tensor_f32(&self) -> Option<Tensor<f32>> {
+	self.to_f32().map(|values| Tensor::<f32>::from_vector(tensor_shape(&self.shape), values.into_vec()))
+    }
+
+    /// Converts real values into Tensor<f64>. Returns None for complex and structured arrays.
+    pub fn to_tensor_f64(&self) -> Option<Tensor<f64>> {
+	self.to_f64().map(|values| Tensor::<f64>::from_vector(tensor_shape(&self.shape), values.into_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_complex::Complex;
+
+    use crate::NpyData;
+    use super::*;
+
+    #[test]
+    fn real_values() {
+	let array = NpyArray { shape: vec![2, 3], data: NpyData::Int16(vec![0, 1, -2, 3, 4, 5].into_boxed_slice()) };
+	let tensor = array.to_tensor_f32().unwrap();
+	assert_eq!(tensor.shape(), &[2, 3]);
+	assert_eq!(tensor.buffer(), &[0.0, 1.0, -2.0, 3.0, 4.0, 5.0]);
+	let tensor = array.to_tensor_f64().unwrap();
+	assert_eq!(tensor.buffer(), &[0.0, 1.0, -2.0, 3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn scalar() {
+	let array = NpyArray { shape: vec![], data: NpyData::Float64(vec![2.5].into_boxed_slice()) };
+	let tensor = array.to_tensor_f32().unwrap();
+	assert_eq!(tensor.shape(), &[1]);
+	assert_eq!(tensor.buffer(), &[2.5]);
+    }
+
+    #[test]
+    fn not_real_values() {
+	let array = NpyArray { shape: vec![1], data: NpyData::Complex64(vec![Complex::new(1.0, 2.0)].into_boxed_slice()) };
+	assert!(array.to_tensor_f32().is_none());
+	let field = NpyArray { shape: vec![1], data: NpyData::UInt8(vec![1].into_boxed_slice()) };
+	let array = NpyArray { shape: vec![1], data: NpyData::Record(vec![("a".to_string(), field)]) };
+	assert!(array.to_tensor_f64().is_none());
+    }
+}
@@ -0,0 +1,72 @@
+// fixtures shared by the unit tests of the modules
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+use half::f16;
+use num_complex::Complex;
+
+use crate::{NpyArray, NpyData, NpyVersion, write_npy_to};
+
+// npy bytes of the header as is
+pub(crate) fn npy_raw(major:u8, header:&[u8], data:&[u8]) -> Vec<u8> {
+    let mut bytes = b"\x93NUMPY".to_vec();
+    bytes.extend_from_slice(&[major, 0]);
+    if major == 1 {
+	bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    }
+    else {
+	bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    }
+    bytes.extend_from_slice(header);
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+// npy v1.0 bytes with the header padded to 64 bytes
+pub(crate) fn npy_bytes(descr:&str, fortran_order:bool, shape:&str, data:&[u8]) -> Vec<u8> {
+    let mut header = format!("{{'descr': {}, 'fortran_order': {}, 'shape': {}, }}",
+			     descr, if fortran_order { "True" } else { "False" }, shape);
+    while (10 + header.len() + 1) % 64 != 0 {
+	header.push(' ');
+    }
+    header.push('\n');
+    npy_raw(1, header.as_bytes(), data)
+}
+
+pub(crate) fn i64_bytes(values:&[i64]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+pub(crate) fn to_bytes(array:&NpyArray, version:Option<NpyVersion>) -> Vec<u8> {
+    let mut bytes:Vec<u8> = Vec::new();
+    write_npy_to(&mut bytes, array, version).unwrap();
+    bytes
+}
+
+pub(crate) fn temp_path(name:&str) -> PathBuf {
+    env::temp_dir().join(format!("numpy_read_test_{}_{}", process::id(), name))
+}
+
+// one array of shape [2, 2] for each value type, without NaN which never compares equal
+pub(crate) fn arrays() -> Vec<NpyArray> {
+    let data = vec![
+	NpyData::Bool(vec![true, false, false, true].into_boxed_slice()),
+	NpyData::UInt8(vec![0, 1, 128, 255].into_boxed_slice()),
+	NpyData::Int8(vec![0, -1, 127, -128].into_boxed_slice()),
+	NpyData::UInt16(vec![0, 1, 0x1234, u16::MAX].into_boxed_slice()),
+	NpyData::Int16(vec![0, -1, i16::MAX, i16::MIN].into_boxed_slice()),
+	NpyData::UInt32(vec![0, 1, 0x12345678, u32::MAX].into_boxed_slice()),
+	NpyData::Int32(vec![0, -1, i32::MAX, i32::MIN].into_boxed_slice()),
+	NpyData::UInt64(vec![0, 1, 1 << 53, u64::MAX].into_boxed_slice()),
+	NpyData::Int64(vec![0, -1, i64::MAX, i64::MIN].into_boxed_slice()),
+	NpyData::Float16(vec![f16::from_f32(0.0), f16::from_f32(-1.5), f16::MAX, f16::INFINITY].into_boxed_slice()),
+	NpyData::Float32(vec![0.0, -1.5, f32::MAX, f32::NEG_INFINITY].into_boxed_slice()),
+	NpyData::Float64(vec![0.0, -1.5, f64::MIN_POSITIVE, f64::INFINITY].into_boxed_slice()),
+	NpyData::Complex64(vec![Complex::new(0.0, 1.0), Complex::new(-1.5, 2.5), Complex::new(f32::MAX, 0.0),
+				Complex::new(0.0, f32::MIN)].into_boxed_slice()),
+	NpyData::Complex128(vec![Complex::new(0.0, 1.0), Complex::new(-1.5, 2.5), Complex::new(f64::MAX, 0.0),
+				 Complex::new(0.0, f64::MIN)].into_boxed_slice())
+    ];
+    data.into_iter().map(|data| NpyArray { shape: vec![2, 2], data: data }).collect()
+}
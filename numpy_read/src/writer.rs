@@ -0,0 +1,272 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::{NpyArray, NpyData, ValueType};
+use crate::error::NpyError;
+
+/// NPY format version written in the preamble.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum NpyVersion {
+    V1, V2, V3
+}
+
+impl fmt::Display for NpyVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    NpyVersion::V1 => write!(f, "1.0"),
+	    NpyVersion::V2 => write!(f, "2.0"),
+	    NpyVersion::V3 => write!(f, "3.0")
+	}
+    }
+}
+
+const NPY_ALIGNMENT:usize = 64;
+
+fn descr_str(value_type:ValueType) -> &'static str {
+    match value_type {
+	ValueType::Bool => "|b1",
+	ValueType::UInt8 => "|u1",
+	ValueType::Int8 => "|i1",
+	ValueType::UInt16 => "<u2",
+	ValueType::Int16 => "<i2",
+	ValueType::UInt32 => "<u4",
+	ValueType::Int32 => "<i4",
+	ValueType::UInt64 => "<u8",
+	ValueType::Int64 => "<i8",
+	ValueType::Float16 => "<f2",
+	ValueType::Float32 => "<f4",
+	ValueType::Float64 => "<f8",
+	ValueType::Complex64 => "<c8",
+	ValueType::Complex128 => "<c16"
+    }
+}
+
+pub(crate) fn shape_str(shape:&[usize]) -> String {
+    // same representation as python tuple
+    match shape.len() {
+	0 => "()".to_string(),
+	1 => format!("({},)", shape[0]),
+	_ => format!("({})", shape.iter().map(|d| d.to_string()).collect::<Vec<String>>().join(", "))
+    }
+}
+
+pub(crate) fn py_str(s:&str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+// descr of structured array is a list of (name, descr) or (name, descr, shape).
+fn descr_repr(array:&NpyArray) -> String {
+    match &array.data {
+	NpyData::Record(fields) => {
+	    let field_reprs:Vec<String> = fields.iter().map(|(name, field)| {
+		let sub_shape = &field.shape[array.shape.len()..];
+		if sub_shape.is_empty() {
+		    format!("({}, {})", py_str(name), descr_repr(field))
+		}
+		else {
+		    format!("({}, {}, {})", py_str(name), descr_repr(field), shape_str(sub_shape))
+		}
+	    }).collect();
+	    format!("[{}]", field_reprs.join(", "))
+	},
+	_ => py_str(descr_str(array.value_type().unwrap()))
+    }
+}
+
+/// Builds the preamble and the header, padded so that the data starts on a 64 byte boundary.
+fn build_header(array:&NpyArray, version:Option<NpyVersion>) -> Result<Vec<u8>,NpyError> {
+    let dict = format!("{{'descr': {}, 'fortran_order': False, 'shape': {}, }}",
+		       descr_repr(array), shape_str(&array.shape));
+    // field names which are not latin1 can only be written in version 3.0
+    let is_latin1 = dict.chars().all(|c| (c as u32) <= 0xff);
+
+    // magic(6) + version(2) + header length(2 or 4)
+    let padded_length = |preamble_size:usize| -> usize {
+	let dict_length = if is_latin1 { dict.chars().count() } else { dict.len() };
+	let unpadded = preamble_size + dict_length + 1;
+	(unpadded + NPY_ALIGNMENT - 1) / NPY_ALIGNMENT * NPY_ALIGNMENT - preamble_size
+    };
+
+    let version = match version {
+	Some(v) => v,
+	None => {
+	    if !is_latin1 {
+		NpyVersion::V3
+	    }
+	    else if padded_length(10) <= u16::MAX as usize {
+		NpyVersion::V1
+	    }
+	    else {
+		NpyVersion::V2
+	    }
+	}
+    };
+
+    if version != NpyVersion::V3 && !is_latin1 {
+	return Err(NpyError::Unsupported("header is not encodable in latin1. use npy version 3.0".to_string()));
+    }
+
+    let preamble_size = if version == NpyVersion::V1 { 10 } else { 12 };
+    let header_length = padded_length(preamble_size);
+
+    let mut header:Vec<u8> = Vec::with_capacity(preamble_size + header_length);
+    header.extend_from_slice(b"\x93NUMPY");
+    match version {
+	NpyVersion::V1 => {
+	    if header_length > u16::MAX as usize {
+		return Err(NpyError::Unsupported("header is too long for npy version 1.0".to_string()));
+	    }
+	    header.extend_from_slice(&[1, 0]);
+	    header.extend_from_slice(&(header_length as u16).to_le_bytes());
+	},
+	NpyVersion::V2 | NpyVersion::V3 => {
+	    header.extend_from_slice(if version == NpyVersion::V2 { &[2, 0] } else { &[3, 0] });
+	    header.extend_from_slice(&(header_length as u32).to_le_bytes());
+	}
+    }
+    if version == NpyVersion::V3 {
+	header.extend_from_slice(dict.as_bytes());
+    }
+    else {
+	header.extend(dict.chars().map(|c| c as u8));
+    }
+    header.resize(preamble_size + header_length - 1, b' ');
+    header.push(b'\n');
+
+    Ok(header)
+}
+
+fn encode_values(array:&NpyArray) -> Vec<u8> {
+    let mut bytes:Vec<u8> = Vec::new();
+    match &array.data {
+	NpyData::Bool(v) => bytes.extend(v.iter().map(|&x| x as u8)),
+	NpyData::UInt8(v) => bytes.extend_from_slice(v),
+	NpyData::Int8(v) => bytes.extend(v.iter().map(|&x| x as u8)),
+	NpyData::UInt16(v) => v.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+	NpyData::Int16(v) => v.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+	NpyData::UInt32(v) => v.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+	NpyData::Int32(v) => v.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+	NpyData::UInt64(v) => v.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+	NpyData::Int64(v) => v.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+	NpyData::Float16(v) => v.iter().for_each(|x| bytes.extend_from_slice(&x.to_bits().to_le_bytes())),
+	NpyData::Float32(v) => v.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+	NpyData::Float64(v) => v.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+	NpyData::Complex64(v) => v.iter().for_each(|x| {
+	    bytes.extend_from_slice(&x.re.to_le_bytes());
+	    bytes.extend_from_slice(&x.im.to_le_bytes());
+	}),
+	NpyData::Complex128(v) => v.iter().for_each(|x| {
+	    bytes.extend_from_slice(&x.re.to_le_bytes());
+	    bytes.extend_from_slice(&x.im.to_le_bytes());
+	}),
+	NpyData::Record(fields) => {
+	    // interleave the fields record by record
+	    let num_of_records = array.len();
+	    let field_bytes:Vec<Vec<u8>> = fields.iter().map(|(_, field)| encode_values(field)).collect();
+	    for r in 0..num_of_records {
+		for b in field_bytes.iter() {
+		    let chunk = b.len() / num_of_records;
+		    bytes.extend_from_slice(&b[r*chunk..(r+1)*chunk]);
+		}
+	    }
+	}
+    }
+    bytes
+}
+
+/// Writes the array as a C order, little endian npy stream.
+/// When version is None, 1.0 is used unless the header does not fit in it.
+pub fn write_npy_to<W:Write>(w:&mut W, array:&NpyArray, version:Option<NpyVersion>) -> Result<(),NpyError> {
+    let header = build_header(array, version)?;
+    w.write_all(&header)?;
+    w.write_all(&encode_values(array))?;
+    Ok(())
+}
+
+pub fn write_npy(file:&str, array:&NpyArray, version:Option<NpyVersion>) -> Result<(),NpyError> {
+    let mut w = BufWriter::new(File::create(file)?);
+    write_npy_to(&mut w, array, version)?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes arrays into a npz archive in the same way as np.savez (no compression).
+pub fn write_npz(file:&str, arrays:&[(String, NpyArray)], version:Option<NpyVersion>) -> Result<(),NpyError> {
+    let mut archive = zip::ZipWriter::new(File::create(file)?);
+
+    for (name, array) in arrays {
+	let mut bytes:Vec<u8> = Vec::new();
+	write_npy_to(&mut bytes, array, version)?;
+	let options = zip::write::FileOptions::default()
+	    .compression_method(zip::CompressionMethod::Stored)
+	    .large_file(bytes.len() >= u32::MAX as usize);
+	archive.start_file(format!("{}.npy", name), options)?;
+	archive.write_all(&bytes)?;
+    }
+
+    archive.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::read_npy_from;
+    use crate::test_util::{arrays, to_bytes};
+
+    // version and the bytes before the values, that is the preamble and the header
+    fn header_size(bytes:&[u8]) -> (u8, usize) {
+	match bytes[6] {
+	    1 => (1, 10 + u16::from_le_bytes([bytes[8], bytes[9]]) as usize),
+	    major => (major, 12 + u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize)
+	}
+    }
+
+    #[test]
+    fn round_trip() {
+	let versions = [(NpyVersion::V1, 1), (NpyVersion::V2, 2), (NpyVersion::V3, 3)];
+	for array in arrays().iter() {
+	    let value_type = array.value_type().unwrap();
+	    for &(version, major) in versions.iter() {
+		let bytes = to_bytes(array, Some(version));
+		let (written_major, size) = header_size(&bytes);
+		assert_eq!(written_major, major);
+		assert_eq!(size % NPY_ALIGNMENT, 0, "{:?} {:?} is not aligned", value_type, version);
+		assert_eq!(bytes[size-1], b'\n');
+		assert_eq!(bytes.len(), size + 4*value_type.size());
+		assert_eq!(&read_npy_from(Cursor::new(bytes)).unwrap(), array, "{:?} {:?}", value_type, version);
+	    }
+	}
+    }
+
+    #[test]
+    fn default_version() {
+	let bytes = to_bytes(&arrays()[1], None);
+	assert_eq!(header_size(&bytes), (1, 128));
+	assert!(bytes[10..].starts_with(b"{'descr': '|u1', 'fortran_order': False, 'shape': (2, 2), }"));
+    }
+
+    #[test]
+    fn structured_round_trip() {
+	let field = |data:NpyData| NpyArray { shape: vec![3], data: data };
+	let array = NpyArray {
+	    shape: vec![3],
+	    data: NpyData::Record(vec![
+		("x".to_string(), field(NpyData::Float32(vec![1.0, 2.0, 3.0].into_boxed_slice()))),
+		("\u{6e29}\u{5ea6}".to_string(), field(NpyData::Int16(vec![-1, 0, 1].into_boxed_slice())))
+	    ])
+	};
+	// the field name is not latin1, so only version 3.0 can write it
+	let mut bytes:Vec<u8> = Vec::new();
+	assert!(matches!(write_npy_to(&mut bytes, &array, Some(NpyVersion::V1)), Err(NpyError::Unsupported(_))));
+	bytes.clear();
+	write_npy_to(&mut bytes, &array, None).unwrap();
+	let (major, size) = header_size(&bytes);
+	assert_eq!((major, size % NPY_ALIGNMENT), (3, 0));
+	assert_eq!(bytes.len(), size + 3*6);
+	assert_eq!(read_npy_from(Cursor::new(bytes)).unwrap(), array);
+    }
+}
@@ -32,6 +32,17 @@ fn load_weight(weight_file:&str) -> Result<HashMap<String,Tensor<f32>>,String> {
 	return Err("weight file does not exists".to_string());
     }
 
+    // weights saved by np.savez
+    if weight_file_path.extension().map_or(false, |ext| ext == "npz") {
+	let arrays = numpy_read::read_npz(weight_file).map_err(|err| err.to_string())?;
+	return arrays.iter().map(|(name, array)| {
+	    match array.to_tensor_f32() {
+		Some(tensor) => Ok((name.clone(), tensor)),
+		None => Err(format!("{} is not real values", name))
+	    }
+	}).collect();
+    }
+
     Tensor::<f32>::from_hdf5(weight_file)
 }
 